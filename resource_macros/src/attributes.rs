@@ -6,8 +6,12 @@ pub mod derive_attr {
         pub schema_name: Option<syn::Lit>,
         pub pg_table_name: syn::Lit,
         pub sqlite_table_name: syn::Lit,
+        pub mysql_table_name: Option<syn::Lit>,
         pub constraint: syn::Lit,
-        pub primary_key: syn::Lit,
+        pub primary_key: Option<syn::Lit>,
+        pub id: Option<syn::Lit>,
+        pub tuple_key: Option<()>,
+        pub drivers: Option<syn::Lit>,
         pub table_iden: Option<()>,
     }
 }
@@ -19,6 +23,64 @@ pub mod field_attr {
     pub struct Resource {
         pub name: Option<syn::Lit>,
         pub typ: Option<syn::Lit>,
+        pub primary_key: Option<()>,
+        pub json: Option<()>,
         pub fields: Option<syn::Lit>,
     }
 }
+
+/// `crate` is a keyword and can't be parsed by `bae`, so pull `crate = "..."`
+/// out of the `#[resource(...)]` attributes before handing them over.
+pub fn take_crate_path(
+    attrs: &[syn::Attribute],
+) -> syn::Result<(Option<syn::Path>, Vec<syn::Attribute>)> {
+    use proc_macro2::{Delimiter, Group, Punct, Spacing, TokenStream, TokenTree};
+
+    let mut krate = None;
+    let mut rest = Vec::with_capacity(attrs.len());
+    for attr in attrs {
+        let group = match attr.tokens.clone().into_iter().next() {
+            Some(TokenTree::Group(g))
+                if attr.path.is_ident("resource") && g.delimiter() == Delimiter::Parenthesis =>
+            {
+                g
+            }
+            _ => {
+                rest.push(attr.clone());
+                continue;
+            }
+        };
+
+        let mut args: Vec<Vec<TokenTree>> = vec![vec![]];
+        for tt in group.stream() {
+            match &tt {
+                TokenTree::Punct(p) if p.as_char() == ',' => args.push(vec![]),
+                _ => args.last_mut().unwrap().push(tt),
+            }
+        }
+
+        let mut kept = TokenStream::new();
+        for arg in args.into_iter().filter(|a| !a.is_empty()) {
+            match arg.as_slice() {
+                [TokenTree::Ident(key), TokenTree::Punct(eq), TokenTree::Literal(lit)]
+                    if key == "crate" && eq.as_char() == '=' =>
+                {
+                    let lit: syn::LitStr = syn::parse2(TokenTree::Literal(lit.clone()).into())?;
+                    krate = Some(lit.parse()?);
+                }
+                [TokenTree::Ident(key), ..] if key == "crate" => {
+                    return Err(syn::Error::new(key.span(), "expected `crate = \"path\"`"));
+                }
+                _ => {
+                    kept.extend(arg);
+                    kept.extend([TokenTree::Punct(Punct::new(',', Spacing::Alone))]);
+                }
+            }
+        }
+
+        let mut attr = attr.clone();
+        attr.tokens = TokenTree::Group(Group::new(Delimiter::Parenthesis, kept)).into();
+        rest.push(attr);
+    }
+    Ok((krate, rest))
+}
@@ -0,0 +1,802 @@
+use crate::{is_bytes, is_text, option_inner};
+
+/// How bind parameters are written.
+pub(crate) enum Placeholders {
+    /// `$1`, `$2`, ...: bound by number, in any order in the statement.
+    Numbered(&'static str),
+    /// `?`: bound in the order they appear in the statement.
+    Positional(&'static str),
+}
+
+/// Everything backend-specific about the statements `#[derive(Resource)]`
+/// generates. Adding a backend means implementing this and a `Db` variant.
+pub(crate) trait Dialect {
+    fn placeholders(&self) -> Placeholders;
+
+    /// Identifiers are always quoted, so reserved words (`group`, `order`,
+    /// `user`) work and case is kept as written. Double quotes by default,
+    /// as in the SQL standard.
+    fn quote_ident(&self, ident: &str) -> String {
+        quoted(ident, '"')
+    }
+
+    /// Whether `#[resource(typ = ...)]` names a column type here. If so,
+    /// bound values are cast to it and `CREATE TABLE` declares it.
+    fn custom_types(&self) -> bool {
+        false
+    }
+
+    fn cast(&self, value: String, _typ: &str) -> String {
+        value
+    }
+
+    /// A bound value of a `#[resource(json)]` column, which is its JSON text.
+    fn cast_json(&self, value: String) -> String {
+        value
+    }
+
+    /// A column as it appears in a `SELECT` list.
+    fn select_column(&self, column: &Column) -> String {
+        self.quote_ident(&column.name)
+    }
+
+    /// Appended to an `INSERT` to overwrite `fields` when a row with the
+    /// same key already exists.
+    fn on_conflict(&self, table: &Table) -> String;
+
+    /// Bound parameters one statement may take.
+    fn max_params(&self) -> usize;
+
+    /// Whether a multi-row upsert affects exactly one row per row, as
+    /// running them one by one would report.
+    fn batch_upserts(&self) -> bool {
+        true
+    }
+
+    /// SQL type of a Rust type as named by [`rust_type`].
+    fn column_type(&self, rust: &str) -> &'static str;
+
+    fn key_type(&self, rust: &str) -> &'static str {
+        self.column_type(rust)
+    }
+
+    /// Appended to a key column the database numbers itself.
+    fn auto_increment(&self) -> &'static str;
+
+    /// Whether keys can be drawn from a named sequence.
+    fn sequences(&self) -> bool {
+        false
+    }
+
+    /// Reads the key back from an `INSERT`; `None` if the backend can't, in
+    /// which case the driver's last insert id is used.
+    fn returning(&self, key: &str) -> Option<String> {
+        Some(format!("RETURNING {}", self.quote_ident(key)))
+    }
+
+    /// What follows `INSERT INTO table` for a row with nothing but defaults.
+    fn default_values(&self) -> &'static str {
+        "DEFAULT VALUES"
+    }
+
+    fn placeholder(&self, n: usize) -> String {
+        match self.placeholders() {
+            Placeholders::Numbered(prefix) => format!("{prefix}{n}"),
+            Placeholders::Positional(p) => p.to_string(),
+        }
+    }
+
+    fn positional(&self) -> bool {
+        matches!(self.placeholders(), Placeholders::Positional(_))
+    }
+}
+
+pub(crate) struct Postgres;
+
+impl Dialect for Postgres {
+    fn placeholders(&self) -> Placeholders {
+        Placeholders::Numbered("$")
+    }
+
+    fn custom_types(&self) -> bool {
+        true
+    }
+
+    fn auto_increment(&self) -> &'static str {
+        " GENERATED BY DEFAULT AS IDENTITY"
+    }
+
+    fn sequences(&self) -> bool {
+        true
+    }
+
+    fn cast(&self, value: String, typ: &str) -> String {
+        format!("{value}::{typ}")
+    }
+
+    // `Any` binds `NULL` as an integer, which doesn't cast to `JSONB`.
+    fn cast_json(&self, value: String) -> String {
+        format!("{value}::text::JSONB")
+    }
+
+    // Custom types (enums and the like) and `JSONB` can't be decoded
+    // through `Any`, so text and JSON columns are read back as text.
+    fn select_column(&self, column: &Column) -> String {
+        let name = self.quote_ident(&column.name);
+        match &column.typ {
+            Some(_) if is_text(&column.ty) => format!("{name}::text AS {name}"),
+            _ if column.json => format!("{name}::text AS {name}"),
+            _ => name,
+        }
+    }
+
+    fn on_conflict(&self, table: &Table) -> String {
+        match excluded_set(self, table) {
+            Some(set) => format!(
+                "ON CONFLICT ON CONSTRAINT {} DO UPDATE SET {set}",
+                self.quote_ident(&table.constraint)
+            ),
+            None => "ON CONFLICT DO NOTHING".to_string(),
+        }
+    }
+
+    fn max_params(&self) -> usize {
+        65535
+    }
+
+    fn column_type(&self, rust: &str) -> &'static str {
+        match rust {
+            "i8" | "i16" => "SMALLINT",
+            "i32" | "u16" => "INTEGER",
+            "i64" | "u32" => "BIGINT",
+            "f32" => "REAL",
+            "f64" => "DOUBLE PRECISION",
+            "bool" => "BOOLEAN",
+            "[u8]" => "BYTEA",
+            "Value" | "Json" => "JSONB",
+            "[i16]" => "SMALLINT[]",
+            "[i32]" => "INTEGER[]",
+            "[i64]" => "BIGINT[]",
+            "[f32]" => "REAL[]",
+            "[f64]" => "DOUBLE PRECISION[]",
+            "[bool]" => "BOOLEAN[]",
+            "[String]" | "[str]" => "TEXT[]",
+            _ => "TEXT",
+        }
+    }
+}
+
+pub(crate) struct Sqlite;
+
+impl Dialect for Sqlite {
+    fn placeholders(&self) -> Placeholders {
+        Placeholders::Numbered("$")
+    }
+
+    // A single `INTEGER` primary key is the rowid, which SQLite assigns.
+    fn auto_increment(&self) -> &'static str {
+        ""
+    }
+
+    fn on_conflict(&self, table: &Table) -> String {
+        let keys = comma_list(table.keys.iter().map(|k| self.quote_ident(&k.name)));
+        match excluded_set(self, table) {
+            Some(set) => format!("ON CONFLICT ({keys}) DO UPDATE SET {set}"),
+            None => format!("ON CONFLICT ({keys}) DO NOTHING"),
+        }
+    }
+
+    // The default `SQLITE_MAX_VARIABLE_NUMBER` before 3.32. Newer builds take
+    // 32766, but binding `$N` parameters looks each one up by name, which
+    // gets quadratic well before that.
+    fn max_params(&self) -> usize {
+        999
+    }
+
+    fn column_type(&self, rust: &str) -> &'static str {
+        match rust {
+            "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => "INTEGER",
+            "f32" | "f64" => "REAL",
+            "bool" => "BOOLEAN",
+            "[u8]" => "BLOB",
+            _ => "TEXT",
+        }
+    }
+}
+
+pub(crate) struct MySql;
+
+impl Dialect for MySql {
+    fn placeholders(&self) -> Placeholders {
+        Placeholders::Positional("?")
+    }
+
+    fn auto_increment(&self) -> &'static str {
+        " AUTO_INCREMENT"
+    }
+
+    fn returning(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    fn default_values(&self) -> &'static str {
+        "() VALUES ()"
+    }
+
+    // `"` only quotes identifiers with `ANSI_QUOTES` set.
+    fn quote_ident(&self, ident: &str) -> String {
+        quoted(ident, '`')
+    }
+
+    // The `Any` driver can't decode `JSON` columns, so they're read back as text.
+    fn select_column(&self, column: &Column) -> String {
+        let name = self.quote_ident(&column.name);
+        match column.json {
+            true => format!("CAST({name} AS CHAR) AS {name}"),
+            false => name,
+        }
+    }
+
+    fn on_conflict(&self, table: &Table) -> String {
+        // There is nothing to overwrite, but a no-op assignment still turns
+        // the duplicate key error off.
+        let set = if table.fields.is_empty() {
+            comma_list(table.keys.iter().map(|k| {
+                let k = self.quote_ident(&k.name);
+                format!("{k} = {k}")
+            }))
+        } else {
+            comma_list(table.fields.iter().map(|f| {
+                let f = self.quote_ident(&f.name);
+                format!("{f} = VALUES({f})")
+            }))
+        };
+        format!("ON DUPLICATE KEY UPDATE {set}")
+    }
+
+    fn max_params(&self) -> usize {
+        65535
+    }
+
+    // An updated row counts twice and an unchanged one once.
+    fn batch_upserts(&self) -> bool {
+        false
+    }
+
+    fn column_type(&self, rust: &str) -> &'static str {
+        match rust {
+            "i8" | "i16" => "SMALLINT",
+            "i32" | "u16" => "INTEGER",
+            "i64" | "u32" => "BIGINT",
+            "u64" => "BIGINT UNSIGNED",
+            "f32" => "REAL",
+            "f64" => "DOUBLE PRECISION",
+            "bool" => "BOOLEAN",
+            "[u8]" => "LONGBLOB",
+            "Value" | "Json" => "JSON",
+            _ => "TEXT",
+        }
+    }
+
+    // MySQL can't index unbounded text.
+    fn key_type(&self, rust: &str) -> &'static str {
+        match self.column_type(rust) {
+            "TEXT" => "VARCHAR(255)",
+            typ => typ,
+        }
+    }
+}
+
+/// `field = EXCLUDED.field` for every field, `None` if there are none.
+fn excluded_set(dialect: &dyn Dialect, table: &Table) -> Option<String> {
+    if table.fields.is_empty() {
+        return None;
+    }
+    Some(comma_list(table.fields.iter().map(|f| {
+        let f = dialect.quote_ident(&f.name);
+        format!("{f} = EXCLUDED.{f}")
+    })))
+}
+
+/// `ident` between `quote`s, any `quote` in it doubled.
+fn quoted(ident: &str, quote: char) -> String {
+    let escaped = ident.replace(quote, &format!("{quote}{quote}"));
+    format!("{quote}{escaped}{quote}")
+}
+
+fn comma_list(items: impl Iterator<Item = String>) -> String {
+    items.intersperse(", ".to_string()).collect()
+}
+
+/// The name [`Dialect::column_type`] is looked up by, and whether the
+/// column is nullable: the last path segment, `[u8]` for byte buffers, or
+/// `[T]` for other `Vec<T>`s.
+pub(crate) fn rust_type(ty: &syn::Type) -> (String, bool) {
+    if let Some(inner) = option_inner(ty) {
+        return (rust_type(inner).0, true);
+    }
+    let name = match ty {
+        _ if is_bytes(ty) => "[u8]".to_string(),
+        syn::Type::Reference(r) => return rust_type(&r.elem),
+        syn::Type::Path(p) => match p.path.segments.last() {
+            Some(seg) if seg.ident == "Vec" => match &seg.arguments {
+                syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(syn::GenericArgument::Type(t)) => format!("[{}]", rust_type(t).0),
+                    _ => String::new(),
+                },
+                _ => String::new(),
+            },
+            Some(seg) => seg.ident.to_string(),
+            None => String::new(),
+        },
+        _ => String::new(),
+    };
+    (name, false)
+}
+
+/// Who picks a new row's key.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeySource {
+    /// `GenResourceID::gen_id_with`.
+    Client,
+    /// The database, numbering the key column.
+    AutoIncrement,
+    /// The database, from the `<table>_<key>_seq` sequence where there are
+    /// sequences, like `AutoIncrement` elsewhere.
+    Sequence,
+}
+
+pub(crate) struct Column {
+    pub name: String,
+    pub ty: syn::Type,
+    pub typ: Option<String>,
+    /// Holds JSON text, bound and read back as a string.
+    pub json: bool,
+}
+
+/// A resource's table as one backend sees it.
+pub(crate) struct Table {
+    pub schema: Option<String>,
+    pub name: String,
+    pub keys: Vec<Column>,
+    pub fields: Vec<Column>,
+    pub constraint: String,
+    /// Whether rows are read back with their keys.
+    pub keys_in_row: bool,
+    pub key_source: KeySource,
+}
+
+/// Every statement a `Resource<DB>` impl runs. Keys are bound before fields,
+/// except in `update` and `patch` with positional placeholders, where they
+/// come last.
+pub(crate) struct Statements {
+    pub insert: String,
+    pub upsert: String,
+    pub update: String,
+    pub delete: String,
+    pub select: String,
+    pub exists: String,
+    /// `UPDATE ... SET {} WHERE ...`, the `SET` list assembled at runtime.
+    pub patch: String,
+    /// `column = placeholder` per field; numbered placeholders leave their
+    /// number as a `{}`, starting after the keys.
+    pub patch_assigns: Vec<String>,
+    /// `insert` without the key, reading back the one the database assigns.
+    /// Only for keys the client doesn't generate.
+    pub insert_assigned: Option<String>,
+    /// `INSERT INTO ... VALUES`, followed by one `( ... )` per row of a batch.
+    pub insert_into: String,
+    /// A batch row's placeholder per column, numbered ones as in `patch_assigns`.
+    pub values_row: Vec<String>,
+    /// What `upsert` appends to `insert`.
+    pub on_conflict: String,
+    /// Rows one batched insert takes; upserts take as many, or just one when
+    /// a batch would report different row counts.
+    pub batch_inserts: usize,
+    pub batch_upserts: usize,
+    pub create_table: String,
+    pub drop_table: String,
+}
+
+impl Statements {
+    pub(crate) fn new(dialect: &dyn Dialect, table: &Table) -> Self {
+        let Table {
+            schema,
+            name,
+            keys,
+            fields,
+            constraint,
+            keys_in_row,
+            key_source,
+        } = table;
+        let q = |ident: &str| dialect.quote_ident(ident);
+        let table_name = match schema {
+            Some(schema) => format!("{}.{}", q(schema), q(name)),
+            None => q(name),
+        };
+        let cast = |column: &Column, placeholder: String| match &column.typ {
+            Some(typ) if dialect.custom_types() => dialect.cast(placeholder, typ),
+            _ if column.json => dialect.cast_json(placeholder),
+            _ => placeholder,
+        };
+        let value = |column: &Column, n: usize| cast(column, dialect.placeholder(n));
+
+        let all = || keys.iter().chain(fields);
+        let columns = comma_list(all().map(|c| q(&c.name)));
+        let values = comma_list(all().enumerate().map(|(i, c)| value(c, i + 1)));
+        let by_pk: String = keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| format!("{} = {}", q(&k.name), dialect.placeholder(i + 1)))
+            .intersperse(" AND ".to_string())
+            .collect();
+        // A resource without any fields still needs a SET clause, so touch
+        // the keys instead.
+        let set = if fields.is_empty() {
+            comma_list(
+                keys.iter()
+                    .map(|k| format!("{} = {}", q(&k.name), q(&k.name))),
+            )
+        } else {
+            let offset = if dialect.positional() {
+                1
+            } else {
+                keys.len() + 1
+            };
+            comma_list(
+                fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| format!("{} = {}", q(&f.name), value(f, i + offset))),
+            )
+        };
+        let template = |column: &Column| {
+            let placeholder = match dialect.placeholders() {
+                Placeholders::Numbered(prefix) => format!("{prefix}{{}}"),
+                Placeholders::Positional(p) => p.to_string(),
+            };
+            cast(column, placeholder)
+        };
+        let patch_assigns = fields
+            .iter()
+            .map(|f| format!("{} = {}", q(&f.name), template(f)))
+            .collect();
+        let read = keys.iter().filter(|_| *keys_in_row).chain(fields);
+        let select = comma_list(read.map(|c| dialect.select_column(c)));
+
+        let key_source = match key_source {
+            KeySource::Sequence if !dialect.sequences() => KeySource::AutoIncrement,
+            source => *source,
+        };
+        let sequence = q(&format!("{name}_{}_seq", keys[0].name));
+        let sequence = match schema {
+            Some(schema) => format!("{}.{sequence}", q(schema)),
+            None => sequence,
+        };
+        let definitions = keys
+            .iter()
+            .map(|k| {
+                let typ = dialect.key_type(&rust_type(&k.ty).0);
+                let default = match key_source {
+                    KeySource::Client => String::new(),
+                    KeySource::AutoIncrement => dialect.auto_increment().to_string(),
+                    KeySource::Sequence => {
+                        format!(" DEFAULT nextval('{}')", sequence.replace('\'', "''"))
+                    }
+                };
+                format!("{} {typ} NOT NULL{default}", q(&k.name))
+            })
+            .chain(fields.iter().map(|f| {
+                let (rust, nullable) = rust_type(&f.ty);
+                let typ = match &f.typ {
+                    Some(typ) if dialect.custom_types() => typ.as_str(),
+                    _ if f.json => dialect.column_type("Json"),
+                    _ => dialect.column_type(&rust),
+                };
+                let null = if nullable { "" } else { " NOT NULL" };
+                format!("{} {typ}{null}", q(&f.name))
+            }));
+        let definitions = comma_list(definitions);
+        let pkey = comma_list(keys.iter().map(|k| q(&k.name)));
+
+        let insert_into = format!("INSERT INTO {table_name} ( {columns} ) VALUES");
+        let insert = format!("{insert_into} ( {values} )");
+        let insert_assigned = (key_source != KeySource::Client).then(|| {
+            let insert = if fields.is_empty() {
+                format!("INSERT INTO {table_name} {}", dialect.default_values())
+            } else {
+                let columns = comma_list(fields.iter().map(|f| q(&f.name)));
+                let values = comma_list(fields.iter().enumerate().map(|(i, f)| value(f, i + 1)));
+                format!("INSERT INTO {table_name} ( {columns} ) VALUES ( {values} )")
+            };
+            match dialect.returning(&keys[0].name) {
+                Some(returning) => format!("{insert} {returning}"),
+                None => insert,
+            }
+        });
+        let create_table = format!(
+            "CREATE TABLE IF NOT EXISTS {table_name} ( {definitions}, \
+             CONSTRAINT {} PRIMARY KEY ({pkey}) )",
+            q(constraint)
+        );
+        // Owned by the key column, so dropping the table drops it too.
+        let create_table = match key_source {
+            KeySource::Sequence => format!(
+                "CREATE SEQUENCE IF NOT EXISTS {sequence}; {create_table}; \
+                 ALTER SEQUENCE {sequence} OWNED BY {table_name}.{}",
+                q(&keys[0].name)
+            ),
+            _ => create_table,
+        };
+        let on_conflict = dialect.on_conflict(table);
+        // Rows without a key need their own statement to read it back.
+        let batch_inserts = match key_source {
+            KeySource::Client => dialect.max_params() / (keys.len() + fields.len()).max(1),
+            _ => 1,
+        };
+        // Without fields to update, a conflicting row is left alone and
+        // doesn't count.
+        let batch_upserts = if dialect.batch_upserts() && !fields.is_empty() {
+            batch_inserts
+        } else {
+            1
+        };
+        Statements {
+            upsert: format!("{insert} {on_conflict}"),
+            insert,
+            insert_into,
+            values_row: all().map(template).collect(),
+            on_conflict,
+            batch_inserts,
+            batch_upserts,
+            update: format!("UPDATE {table_name} SET {set} WHERE {by_pk}"),
+            delete: format!("DELETE FROM {table_name} WHERE {by_pk}"),
+            select: format!("SELECT {select} FROM {table_name} WHERE {by_pk}"),
+            exists: format!("SELECT 1 FROM {table_name} WHERE {by_pk} LIMIT 1"),
+            patch: format!("UPDATE {table_name} SET {{}} WHERE {by_pk}"),
+            patch_assigns,
+            insert_assigned,
+            create_table,
+            drop_table: format!("DROP TABLE IF EXISTS {table_name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Column, Dialect, KeySource, MySql, Postgres, Sqlite, Statements, Table};
+
+    fn member() -> Table {
+        let column = |name: &str, ty: syn::Type, typ: Option<&str>| Column {
+            name: name.to_string(),
+            ty,
+            typ: typ.map(str::to_string),
+            json: false,
+        };
+        Table {
+            schema: Some("slep".to_string()),
+            name: "group_member".to_string(),
+            keys: vec![
+                column("id", syn::parse_quote!(i64), None),
+                column("gid", syn::parse_quote!(i64), None),
+            ],
+            fields: vec![
+                column("role", syn::parse_quote!(&'a str), Some("slep.role")),
+                column("nick", syn::parse_quote!(Option<String>), None),
+            ],
+            constraint: "group_member_pkey".to_string(),
+            keys_in_row: false,
+            key_source: KeySource::Client,
+        }
+    }
+
+    fn sqlite_member() -> Table {
+        Table {
+            schema: None,
+            ..member()
+        }
+    }
+
+    #[test]
+    fn postgres() {
+        let s = Statements::new(&Postgres, &member());
+        assert_eq!(
+            s.insert,
+            r#"INSERT INTO "slep"."group_member" ( "id", "gid", "role", "nick" ) "#.to_string()
+                + "VALUES ( $1, $2, $3::slep.role, $4 )"
+        );
+        assert_eq!(
+            s.upsert,
+            format!(
+                r#"{} ON CONFLICT ON CONSTRAINT "group_member_pkey" DO UPDATE SET "#,
+                s.insert
+            ) + r#""role" = EXCLUDED."role", "nick" = EXCLUDED."nick""#
+        );
+        assert_eq!(
+            s.update,
+            r#"UPDATE "slep"."group_member" SET "role" = $3::slep.role, "nick" = $4 "#.to_string()
+                + r#"WHERE "id" = $1 AND "gid" = $2"#
+        );
+        assert_eq!(
+            s.select,
+            r#"SELECT "role"::text AS "role", "nick" FROM "slep"."group_member" "#.to_string()
+                + r#"WHERE "id" = $1 AND "gid" = $2"#
+        );
+        assert_eq!(
+            s.patch_assigns,
+            [r#""role" = ${}::slep.role"#, r#""nick" = ${}"#]
+        );
+        assert_eq!(s.values_row, ["${}", "${}", "${}::slep.role", "${}"]);
+        assert_eq!(
+            s.insert_into,
+            r#"INSERT INTO "slep"."group_member" ( "id", "gid", "role", "nick" ) VALUES"#
+        );
+        assert_eq!((s.batch_inserts, s.batch_upserts), (16383, 16383));
+        assert_eq!(
+            s.create_table,
+            r#"CREATE TABLE IF NOT EXISTS "slep"."group_member" ( "id" BIGINT NOT NULL, "#
+                .to_string()
+                + r#""gid" BIGINT NOT NULL, "role" slep.role NOT NULL, "nick" TEXT, "#
+                + r#"CONSTRAINT "group_member_pkey" PRIMARY KEY ("id", "gid") )"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        let s = Statements::new(&Sqlite, &sqlite_member());
+        assert_eq!(
+            s.upsert,
+            r#"INSERT INTO "group_member" ( "id", "gid", "role", "nick" ) "#.to_string()
+                + r#"VALUES ( $1, $2, $3, $4 ) ON CONFLICT ("id", "gid") "#
+                + r#"DO UPDATE SET "role" = EXCLUDED."role", "nick" = EXCLUDED."nick""#
+        );
+        assert_eq!(
+            s.update,
+            r#"UPDATE "group_member" SET "role" = $3, "nick" = $4 WHERE "id" = $1 AND "gid" = $2"#
+        );
+        assert_eq!(
+            s.delete,
+            r#"DELETE FROM "group_member" WHERE "id" = $1 AND "gid" = $2"#
+        );
+        assert_eq!(
+            s.create_table,
+            r#"CREATE TABLE IF NOT EXISTS "group_member" ( "id" INTEGER NOT NULL, "#.to_string()
+                + r#""gid" INTEGER NOT NULL, "role" TEXT NOT NULL, "nick" TEXT, "#
+                + r#"CONSTRAINT "group_member_pkey" PRIMARY KEY ("id", "gid") )"#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        let s = Statements::new(&MySql, &sqlite_member());
+        assert_eq!(
+            s.upsert,
+            "INSERT INTO `group_member` ( `id`, `gid`, `role`, `nick` ) VALUES ( ?, ?, ?, ? ) \
+             ON DUPLICATE KEY UPDATE `role` = VALUES(`role`), `nick` = VALUES(`nick`)"
+        );
+        assert_eq!(
+            s.update,
+            "UPDATE `group_member` SET `role` = ?, `nick` = ? WHERE `id` = ? AND `gid` = ?"
+        );
+        assert_eq!(s.patch_assigns, ["`role` = ?", "`nick` = ?"]);
+        assert_eq!(s.values_row, ["?", "?", "?", "?"]);
+        assert_eq!((s.batch_inserts, s.batch_upserts), (16383, 1));
+        assert!(MySql.positional() && !Postgres.positional());
+    }
+
+    #[test]
+    fn keys_only() {
+        let table = Table {
+            fields: vec![],
+            ..sqlite_member()
+        };
+        let s = Statements::new(&Sqlite, &table);
+        assert_eq!(s.on_conflict, r#"ON CONFLICT ("id", "gid") DO NOTHING"#);
+        assert_eq!((s.batch_inserts, s.batch_upserts), (499, 1));
+        assert_eq!(
+            s.update,
+            r#"UPDATE "group_member" SET "id" = "id", "gid" = "gid" WHERE "id" = $1 AND "gid" = $2"#
+        );
+        let s = Statements::new(&MySql, &table);
+        assert!(s
+            .upsert
+            .ends_with("ON DUPLICATE KEY UPDATE `id` = `id`, `gid` = `gid`"));
+        assert_eq!(
+            s.update,
+            "UPDATE `group_member` SET `id` = `id`, `gid` = `gid` WHERE `id` = ? AND `gid` = ?"
+        );
+    }
+
+    #[test]
+    fn assigned_keys() {
+        let table = |key_source| Table {
+            keys: vec![Column {
+                name: "id".to_string(),
+                ty: syn::parse_quote!(i64),
+                typ: None,
+                json: false,
+            }],
+            fields: member().fields.split_off(1),
+            key_source,
+            ..member()
+        };
+
+        let s = Statements::new(&Postgres, &table(KeySource::AutoIncrement));
+        assert_eq!(
+            s.insert_assigned.unwrap(),
+            r#"INSERT INTO "slep"."group_member" ( "nick" ) VALUES ( $1 ) RETURNING "id""#
+        );
+        assert!(s
+            .create_table
+            .contains(r#""id" BIGINT NOT NULL GENERATED BY DEFAULT AS IDENTITY,"#));
+        assert_eq!(s.batch_inserts, 1);
+        let s = Statements::new(&Postgres, &table(KeySource::Sequence));
+        assert!(s.create_table.starts_with(
+            r#"CREATE SEQUENCE IF NOT EXISTS "slep"."group_member_id_seq"; CREATE TABLE"#
+        ));
+        assert!(s
+            .create_table
+            .contains(r#""id" BIGINT NOT NULL DEFAULT nextval('"slep"."group_member_id_seq"'),"#));
+        assert!(s.create_table.ends_with(
+            r#"ALTER SEQUENCE "slep"."group_member_id_seq" OWNED BY "slep"."group_member"."id""#
+        ));
+
+        // No sequences, so the rowid.
+        let s = Statements::new(&Sqlite, &table(KeySource::Sequence));
+        assert!(s.create_table.contains(r#""id" INTEGER NOT NULL, "#));
+        let s = Statements::new(
+            &MySql,
+            &Table {
+                fields: vec![],
+                ..table(KeySource::AutoIncrement)
+            },
+        );
+        assert_eq!(
+            s.insert_assigned.unwrap(),
+            "INSERT INTO `slep`.`group_member` () VALUES ()"
+        );
+        assert!(s
+            .create_table
+            .contains("`id` BIGINT NOT NULL AUTO_INCREMENT,"));
+        assert_eq!(Statements::new(&Sqlite, &member()).insert_assigned, None);
+    }
+
+    #[test]
+    fn json_columns() {
+        let table = Table {
+            fields: vec![Column {
+                name: "tags".to_string(),
+                ty: syn::parse_quote!(Option<Vec<String>>),
+                typ: None,
+                json: true,
+            }],
+            ..member()
+        };
+
+        let s = Statements::new(&Postgres, &table);
+        assert!(s.insert.ends_with("VALUES ( $1, $2, $3::text::JSONB )"));
+        assert_eq!(s.patch_assigns, [r#""tags" = ${}::text::JSONB"#]);
+        assert!(s
+            .select
+            .starts_with(r#"SELECT "tags"::text AS "tags" FROM"#));
+        assert!(s.create_table.contains(r#""tags" JSONB, "#));
+        let s = Statements::new(&Sqlite, &table);
+        assert!(s.insert.ends_with("VALUES ( $1, $2, $3 )"));
+        assert!(s.select.starts_with(r#"SELECT "tags" FROM"#));
+        assert!(s.create_table.contains(r#""tags" TEXT, "#));
+        let s = Statements::new(&MySql, &table);
+        assert!(s
+            .select
+            .starts_with("SELECT CAST(`tags` AS CHAR) AS `tags` FROM"));
+        assert!(s.create_table.contains("`tags` JSON, "));
+    }
+
+    #[test]
+    fn quote_ident() {
+        assert_eq!(Postgres.quote_ident("group"), r#""group""#);
+        assert_eq!(Sqlite.quote_ident("userId"), r#""userId""#);
+        assert_eq!(Postgres.quote_ident(r#"a"b"#), r#""a""b""#);
+        assert_eq!(MySql.quote_ident("order"), "`order`");
+        assert_eq!(MySql.quote_ident("a`b"), "`a``b`");
+    }
+}
@@ -6,6 +6,10 @@ use quote::{format_ident, quote};
 use syn::{parse_macro_input, DeriveInput, Error};
 
 mod attributes;
+mod dialect;
+mod resources;
+
+use dialect::{rust_type, Column, Dialect, KeySource, Statements, Table};
 
 #[proc_macro_derive(Resource, attributes(resource))]
 pub fn derive_entity(input: TokenStream) -> TokenStream {
@@ -15,392 +19,1342 @@ pub fn derive_entity(input: TokenStream) -> TokenStream {
         .into()
 }
 
+#[proc_macro_derive(Resources, attributes(resource))]
+pub fn derive_resources(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    resources::DeriveResources::new(input)
+        .map(|derive| derive.expand())
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
 fn expand_derive_entity(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     Ok(DeriveResource::new(input)?.expand())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Db {
+    Postgres,
+    Sqlite,
+    MySql,
+}
+
+impl Db {
+    fn dialect(self) -> &'static dyn Dialect {
+        match self {
+            Db::Postgres => &dialect::Postgres,
+            Db::Sqlite => &dialect::Sqlite,
+            Db::MySql => &dialect::MySql,
+        }
+    }
+
+    fn path(self, krate: &syn::Path) -> proc_macro2::TokenStream {
+        match self {
+            Db::Postgres => quote!(#krate::sqlx::Postgres),
+            Db::Sqlite => quote!(#krate::sqlx::Sqlite),
+            Db::MySql => quote!(#krate::sqlx::MySql),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Field {
     ident: syn::Ident,
+    vis: syn::Visibility,
+    ty: syn::Type,
     name: String,
     typ: Option<String>,
+    /// Stored as the value's JSON text, from `#[resource(json)]`.
+    json: bool,
+}
+
+/// How `#[resource(id = "...")]` keys new rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdStrategy {
+    /// The resource's own `SnowflakeID` generator, into an `i64` key.
+    Snowflake,
+    UuidV4,
+    UuidV7,
+    DbSequence,
+    AutoIncrement,
+}
+
+impl IdStrategy {
+    fn parse(lit: &syn::Lit, keys: usize) -> syn::Result<Self> {
+        let syn::Lit::Str(s) = lit else {
+            return Err(syn::Error::new_spanned(lit, "attribute must be a string"));
+        };
+        let id = match s.value().as_str() {
+            "snowflake" => IdStrategy::Snowflake,
+            "uuid_v4" => IdStrategy::UuidV4,
+            "uuid_v7" => IdStrategy::UuidV7,
+            "db_sequence" => IdStrategy::DbSequence,
+            "autoincrement" => IdStrategy::AutoIncrement,
+            _ => {
+                let expected =
+                    "expected `snowflake`, `uuid_v4`, `uuid_v7`, `db_sequence` or `autoincrement`";
+                return Err(syn::Error::new_spanned(lit, expected));
+            }
+        };
+        if keys != 1 {
+            return Err(syn::Error::new_spanned(
+                lit,
+                "`id` needs a primary key of exactly one column",
+            ));
+        }
+        Ok(id)
+    }
+
+    /// The database picks the key, so it's left out of `INSERT`.
+    fn assigned(self) -> bool {
+        matches!(self, IdStrategy::DbSequence | IdStrategy::AutoIncrement)
+    }
 }
 
 #[derive(Debug)]
 struct DeriveResource {
+    krate: syn::Path,
+    struct_vis: syn::Visibility,
     struct_ident: syn::Ident,
     struct_generics: syn::Generics,
     schema_name: Option<syn::Ident>,
     pg_table_name: String,
     sqlite_table_name: String,
-    primary_keys: Vec<(syn::Ident, syn::Ident)>,
+    mysql_table_name: String,
+    primary_keys: Vec<Field>,
+    /// Keys declared with `#[resource(primary_key)]`, so the struct holds
+    /// them too and they're read back with it.
+    keys_in_row: bool,
+    /// Keep a tuple `ResourceID` for composite keys instead of `<Name>Key`.
+    tuple_key: bool,
+    /// `(database, native)` per `Resource` impl emitted.
+    drivers: Vec<(Db, bool)>,
     constraint: String,
+    id: Option<IdStrategy>,
     fields: Vec<Field>,
 }
 
 impl DeriveResource {
     fn new(input: syn::DeriveInput) -> Result<Self, syn::Error> {
+        let (krate, attrs) = attributes::take_crate_path(&input.attrs)?;
+        let krate = krate.unwrap_or_else(|| syn::parse_quote!(::resource));
         let attributes::derive_attr::Resource {
             schema_name,
             pg_table_name,
             sqlite_table_name,
+            mysql_table_name,
             constraint,
             primary_key,
+            id,
+            tuple_key,
+            drivers,
             table_iden: _,
-        } = attributes::derive_attr::Resource::from_attributes(&input.attrs)?;
+        } = attributes::derive_attr::Resource::from_attributes(&attrs)?;
+        let tuple_key = tuple_key.is_some();
+        let drivers = parse_drivers(drivers.as_ref())?;
+        let struct_vis = input.vis;
         let struct_ident = input.ident;
         let struct_generics = input.generics;
 
         let schema_name = schema_name
-            .and_then(|s| parse_lit_string(&s).ok())
+            .map(|s| parse_lit_string(&s))
+            .transpose()?
             .map(|s| format_ident!("{s}"));
         let pg_table_name = parse_lit_string(&pg_table_name)?.to_string();
         let sqlite_table_name = parse_lit_string(&sqlite_table_name)?.to_string();
+        let mysql_table_name = match mysql_table_name {
+            Some(name) => parse_lit_string(&name)?.to_string(),
+            None => sqlite_table_name.clone(),
+        };
         let constraint = parse_lit_string(&(constraint))?.to_string();
-        let pkey = parse_lit_string(&(primary_key))?;
-        let mut pkey = pkey.to_string();
-        pkey.retain(|c| c != ' ');
-        let primary_keys: Vec<(syn::Ident, syn::Ident)> = pkey
-            .as_str()
-            .split(',')
-            .map(|s| s.split_once(':').unwrap())
-            .map(|(n, ty)| (format_ident!("{n}"), format_ident!("{ty}")))
-            .collect();
 
-        let fields: Vec<Field> = if let syn::Data::Struct(item_struct) = input.data
-        && let syn::Fields::Named(fields) = item_struct.fields {
-            fields.named.iter().filter_map(|field| {
-                let ident = field.ident.as_ref()?;
-                let field_attr =  attributes::field_attr::Resource::try_from_attributes(&field.attrs).ok()?;
-                let (name, typ) =
-                if let Some(attr) = field_attr
-                && let Some(name) = attr.name {
-                    let name = parse_lit_string(&name).unwrap().to_string();
-                    let typ = attr.typ.and_then(|t| parse_lit_string(&t).ok()).map(|t| t.to_string());
-                    (name, typ)
-                } else {
-                    let original_field_name = trim_starting_raw_identifier(ident);
-                    use heck::ToSnakeCase as _;
-                    let name = original_field_name.as_str().to_snake_case();
-                    (name, None)
+        let mut fields = Vec::new();
+        let mut key_fields = Vec::new();
+        if let syn::Data::Struct(item_struct) = input.data
+            && let syn::Fields::Named(named) = item_struct.fields
+        {
+            for field in named.named {
+                let Some(ident) = field.ident else {
+                    continue;
                 };
-                Some(Field { name, typ, ident: ident.clone() })
-            }).collect()
-        } else {
-            vec![]
+                let attributes::field_attr::Resource {
+                    name,
+                    typ,
+                    primary_key,
+                    json,
+                    fields: _,
+                } = attributes::field_attr::Resource::try_from_attributes(&field.attrs)?
+                    .unwrap_or_default();
+                let name = match name {
+                    Some(name) => parse_lit_string(&name)?.to_string(),
+                    None => {
+                        use heck::ToSnakeCase as _;
+                        trim_starting_raw_identifier(&ident).to_snake_case()
+                    }
+                };
+                if primary_key.is_some()
+                    && let Some(typ) = &typ
+                {
+                    return Err(syn::Error::new_spanned(
+                        typ,
+                        "`typ` isn't supported on key columns",
+                    ));
+                }
+                if primary_key.is_some() && json.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        &ident,
+                        "`json` isn't supported on key columns",
+                    ));
+                }
+                let typ = typ
+                    .map(|t| parse_lit_string(&t))
+                    .transpose()?
+                    .map(|t| t.to_string());
+                let field = Field {
+                    name,
+                    typ,
+                    json: json.is_some(),
+                    ident,
+                    vis: field.vis,
+                    ty: field.ty,
+                };
+                if primary_key.is_some() {
+                    key_fields.push(field);
+                } else {
+                    fields.push(field);
+                }
+            }
+        }
+
+        let keys_in_row = !key_fields.is_empty();
+        let primary_keys = match primary_key {
+            Some(lit) if keys_in_row => {
+                return Err(syn::Error::new_spanned(
+                    lit,
+                    "the key is already declared with `#[resource(primary_key)]` on fields",
+                ))
+            }
+            Some(lit) => parse_primary_key(&lit)?,
+            None if keys_in_row => key_fields,
+            None => {
+                return Err(syn::Error::new_spanned(
+                    &struct_ident,
+                    "missing key: add `primary_key = \"id: i64\"` or mark fields with `#[resource(primary_key)]`",
+                ))
+            }
         };
+        let id = id
+            .map(|lit| IdStrategy::parse(&lit, primary_keys.len()))
+            .transpose()?;
+        if primary_keys.len() > 1
+            && !tuple_key
+            && let Some(k) = primary_keys.iter().find(|k| has_lifetime(&k.ty))
+        {
+            return Err(syn::Error::new_spanned(
+                &k.ty,
+                "`<Name>Key` can't borrow; use owned key types or `#[resource(tuple_key)]`",
+            ));
+        }
 
         Ok(DeriveResource {
+            krate,
+            struct_vis,
             struct_ident,
             struct_generics,
             schema_name,
             pg_table_name,
             sqlite_table_name,
+            mysql_table_name,
             primary_keys,
+            keys_in_row,
+            tuple_key,
+            drivers,
             constraint,
+            id,
             fields,
         })
     }
 
-    fn gen_upsert(&self) -> (String, String, String, String, String, String) {
+    fn table(&self, db: Db) -> Table {
         let Self {
+            krate: _,
+            struct_vis: _,
             struct_ident: _,
             struct_generics: _,
             schema_name,
             pg_table_name,
             sqlite_table_name,
+            mysql_table_name,
             primary_keys,
+            keys_in_row,
+            tuple_key: _,
+            drivers: _,
             constraint,
+            id,
             fields,
         } = self;
 
-        let mut pk_fields: Vec<Field> = primary_keys
-            .clone()
-            .into_iter()
-            .map(|(ident, _)| Field {
-                name: ident.to_string(),
-                ident,
-                typ: None,
-            })
-            .collect();
+        let (schema, name) = match db {
+            Db::Postgres => (schema_name.as_ref().map(|s| s.to_string()), pg_table_name),
+            Db::Sqlite => (None, sqlite_table_name),
+            Db::MySql => (None, mysql_table_name),
+        };
+        Table {
+            schema,
+            name: name.clone(),
+            keys: primary_keys
+                .iter()
+                .map(|k| Column {
+                    name: k.name.clone(),
+                    ty: k.ty.clone(),
+                    typ: None,
+                    json: false,
+                })
+                .collect(),
+            fields: fields
+                .iter()
+                .map(|f| Column {
+                    name: f.name.clone(),
+                    ty: f.ty.clone(),
+                    typ: f.typ.clone(),
+                    json: f.json,
+                })
+                .collect(),
+            constraint: constraint.clone(),
+            keys_in_row: *keys_in_row,
+            key_source: match id {
+                Some(IdStrategy::AutoIncrement) => KeySource::AutoIncrement,
+                Some(IdStrategy::DbSequence) => KeySource::Sequence,
+                _ => KeySource::Client,
+            },
+        }
+    }
+
+    fn gen_table_schema(&self, db: Db) -> proc_macro2::TokenStream {
+        let krate = &self.krate;
+        let Table {
+            schema,
+            name,
+            keys,
+            fields,
+            constraint,
+            keys_in_row: _,
+            key_source: _,
+        } = self.table(db);
 
-        let upsert_set: String = fields
+        let schema = match schema {
+            Some(schema) => quote!(Some(#schema)),
+            None => quote!(None),
+        };
+        let db = db.path(krate);
+        let key_names: Vec<&String> = keys.iter().map(|k| &k.name).collect();
+        let names = keys.iter().chain(&fields).map(|c| &c.name);
+        let nullable = keys
             .iter()
-            .map(|f| format!("{} = EXCLUDED.{}", f.name, f.name))
-            .intersperse(", ".to_string())
-            .collect();
+            .map(|_| false)
+            .chain(fields.iter().map(|f| rust_type(&f.ty).1));
+
+        quote! {
+            #krate::TableSchema {
+                backend: <#db as #krate::sqlx::Database>::NAME,
+                schema: #schema,
+                table: #name,
+                columns: &[ #( #krate::ColumnSchema { name: #names, nullable: #nullable } ),* ],
+                primary_key: &[ #( #key_names ),* ],
+                constraint: #constraint,
+            }
+        }
+    }
+
+    /// `<Name>Patch` mirrors the resource with every column wrapped in an `Option`,
+    /// so a patch only names the columns it changes.
+    fn gen_patch(&self) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        let Self {
+            krate,
+            struct_vis,
+            struct_ident,
+            struct_generics,
+            schema_name: _,
+            pg_table_name: _,
+            sqlite_table_name: _,
+            mysql_table_name: _,
+            primary_keys: _,
+            keys_in_row: _,
+            tuple_key: _,
+            drivers: _,
+            constraint: _,
+            id: _,
+            fields,
+        } = self;
 
-        let mut fields = fields.clone();
+        let (_, ty_generics, where_clause) = struct_generics.split_for_impl();
+        let patch = format_ident!("{struct_ident}Patch");
+        let serde_crate = quote!(#krate::serde).to_string();
+        let deserialize_with = quote!(#krate::__patch_field).to_string();
+        let patch_fields = fields.iter().map(|f| {
+            let Field { ident, vis, ty, .. } = f;
+            let borrow = has_lifetime(ty).then(|| quote!(borrow,));
+            quote! {
+                #[serde(
+                    #borrow
+                    default,
+                    skip_serializing_if = "Option::is_none",
+                    deserialize_with = #deserialize_with
+                )]
+                #vis #ident: ::std::option::Option<#ty>,
+            }
+        });
 
-        pk_fields.append(&mut fields);
-        let fields = pk_fields;
+        (
+            quote! { #patch #ty_generics },
+            quote! {
+                #[derive(Debug, Clone, Default, PartialEq, #krate::serde::Serialize, #krate::serde::Deserialize)]
+                #[serde(crate = #serde_crate)]
+                #struct_vis struct #patch #struct_generics #where_clause {
+                    #( #patch_fields )*
+                }
+            },
+        )
+    }
 
-        let pg_table_name = if let Some(schema) = schema_name {
-            format!("{schema}.{pg_table_name}")
+    fn gen_patch_fn(
+        &self,
+        db: Db,
+        statements: &Statements,
+        driver: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let Self {
+            krate,
+            struct_vis: _,
+            struct_ident,
+            struct_generics: _,
+            schema_name: _,
+            pg_table_name: _,
+            sqlite_table_name: _,
+            mysql_table_name: _,
+            primary_keys,
+            keys_in_row: _,
+            tuple_key: _,
+            drivers: _,
+            constraint: _,
+            id: _,
+            fields,
+        } = self;
+
+        let resource_name = struct_ident.to_string();
+        let ids: Vec<&syn::Ident> = primary_keys.iter().map(|k| &k.ident).collect();
+        let ids_pat = self.key_bindings();
+        let fs: Vec<&syn::Ident> = fields.iter().map(|f| &f.ident).collect();
+        let vs = fields.iter().map(|f| bind_value(krate, f, quote!(*v)));
+        let update = &statements.patch;
+        let assigns = &statements.patch_assigns;
+
+        // Positional placeholders bind in order, so the keys go after the changes.
+        let (push_assigns, bind_ids_first, bind_ids_last) = if db.dialect().positional() {
+            (
+                quote! { #( if changes.#fs.is_some() { set.push(#assigns.to_string()); } )* },
+                quote!(),
+                quote! { #( query = query.bind(&#ids); )* },
+            )
         } else {
-            pg_table_name.clone()
+            let offset = primary_keys.len() + 1;
+            (
+                quote! {
+                    #(
+                        if changes.#fs.is_some() {
+                            set.push(format!(#assigns, set.len() + #offset));
+                        }
+                    )*
+                },
+                quote! { #(.bind(&#ids))* },
+                quote!(),
+            )
+        };
+        let db = db.path(krate);
+
+        quote! {
+            async fn patch<'c, E>(
+                id: &Self::ResourceID,
+                changes: &Self::Patch,
+                exector: E,
+            ) -> Result<#krate::ExecutionOutcome, #krate::Error>
+            where
+            E: #krate::ResourceExecutor<'c, Database = #driver>,
+            {
+                #krate::ResourceExecutor::ensure_backend::<#db>(&exector)?;
+                let mut set: Vec<String> = Vec::new();
+                #push_assigns
+                if set.is_empty() {
+                    return if <Self as #krate::Resource<#db, #driver>>::exists(id, exector).await? {
+                        Ok(#krate::ExecutionOutcome::new(#krate::ActionKind::Patch, 0, id))
+                    } else {
+                        Err(#krate::Error::not_found(#resource_name, id))
+                    };
+                }
+
+                let #ids_pat = id.clone();
+                let sql = format!(#update, set.join(", "));
+                let mut query = #krate::sqlx::query(&sql) #bind_ids_first;
+                #(
+                    if let Some(v) = &changes.#fs {
+                        query = query.bind(#vs);
+                    }
+                )*
+                #bind_ids_last
+                let res = query.execute(exector).await?;
+                if res.rows_affected() == 0 {
+                    return Err(#krate::Error::not_found(#resource_name, &#ids_pat));
+                }
+            Ok(#krate::ExecutionOutcome::new(
+                #krate::ActionKind::Patch,
+                res.rows_affected(),
+                &#ids_pat,
+            ))
+            }
+        }
+    }
+
+    /// `<Name>Key`, the `ResourceID` of a composite key unless `tuple_key` is set.
+    fn key_struct(&self) -> Option<syn::Ident> {
+        (self.primary_keys.len() > 1 && !self.tuple_key)
+            .then(|| format_ident!("{}Key", self.struct_ident))
+    }
+
+    /// The key's columns as locals, written the same as a pattern or a value
+    /// of `ResourceID`.
+    fn key_bindings(&self) -> proc_macro2::TokenStream {
+        let pks: Vec<&syn::Ident> = self.primary_keys.iter().map(|k| &k.ident).collect();
+        match (self.key_struct(), pks.as_slice()) {
+            (Some(key), _) => quote! { #key { #(#pks),* } },
+            (None, [pk]) => quote! { #pk },
+            (None, _) => quote! { ( #(#pks),* ) },
+        }
+    }
+
+    fn gen_key_struct(&self) -> proc_macro2::TokenStream {
+        let Some(key) = self.key_struct() else {
+            return quote!();
         };
+        let Self {
+            krate,
+            struct_vis,
+            primary_keys,
+            ..
+        } = self;
 
-        let select: String = fields
+        let pks: Vec<&syn::Ident> = primary_keys.iter().map(|k| &k.ident).collect();
+        let tys: Vec<&syn::Type> = primary_keys.iter().map(|k| &k.ty).collect();
+        let serde_crate = quote!(#krate::serde).to_string();
+        let display = primary_keys
             .iter()
-            .map(|f| f.name.to_string())
-            .intersperse(", ".to_string())
-            .collect();
+            .map(|k| format!("{}={{}}", trim_starting_raw_identifier(&k.ident)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        // Byte columns have no `Display` of their own.
+        let args = primary_keys.iter().map(|k| {
+            let ident = &k.ident;
+            match is_bytes(&k.ty) {
+                true => quote!(#krate::__Hex(&self.#ident[..])),
+                false => quote!(self.#ident),
+            }
+        });
+        quote! {
+            #[derive(Debug, Clone, PartialEq, Eq, Hash, #krate::serde::Serialize, #krate::serde::Deserialize)]
+            #[serde(crate = #serde_crate)]
+            #struct_vis struct #key {
+                #( #struct_vis #pks: #tys, )*
+            }
 
-        let pg_values: String = fields
+            #[automatically_derived]
+            impl ::std::fmt::Display for #key {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    write!(f, #display, #( #args ),*)
+                }
+            }
+        }
+    }
+
+    /// The type returned by the read API: `Self` if the resource borrows nothing,
+    /// otherwise a generated `<Name>Owned` twin with every borrow made owned.
+    fn gen_owned(&self) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        let Self {
+            krate,
+            struct_vis,
+            struct_ident,
+            struct_generics,
+            schema_name: _,
+            pg_table_name: _,
+            sqlite_table_name: _,
+            mysql_table_name: _,
+            primary_keys,
+            keys_in_row,
+            tuple_key: _,
+            drivers: _,
+            constraint: _,
+            id: _,
+            fields,
+        } = self;
+
+        // Keys declared on fields are part of the struct as well.
+        let keys = primary_keys.iter().filter(|_| *keys_in_row);
+        let fields: Vec<&Field> = keys.chain(fields).collect();
+        let idents: Vec<&syn::Ident> = fields.iter().map(|f| &f.ident).collect();
+        let tys: Vec<syn::Type> = fields.iter().map(|f| owned_type(&f.ty)).collect();
+        // JSON columns are read as text and deserialized from it.
+        let string: syn::Type = syn::parse_quote!(::std::string::String);
+        let decode_tys: Vec<&syn::Type> = fields
             .iter()
-            .enumerate()
-            .map(|(i, f)| {
-                let mut v = format!("${}", i + 1);
-                if let Some(typ) = &f.typ {
-                    let typ = format!("::{typ}");
-                    let typ = typ.as_str();
-                    v.push_str(typ);
-                };
-                v
+            .zip(&tys)
+            .map(|(f, ty)| match f.json {
+                true => &string,
+                false => option_inner(ty).unwrap_or(ty),
             })
-            .intersperse(", ".to_string())
             .collect();
-        let sqlite_values: String = fields
+        let decodes: Vec<proc_macro2::TokenStream> = fields
             .iter()
-            .enumerate()
-            .map(|(i, _)| format!("${}", i + 1))
-            .intersperse(", ".to_string())
+            .zip(&decode_tys)
+            .map(|(f, ty)| {
+                let name = &f.name;
+                let get = quote! { #krate::sqlx::Row::try_get::<#ty, _>(row, #name)? };
+                let get = match f.json {
+                    true => quote! { #krate::__from_json(#name, &#get)? },
+                    false => get,
+                };
+                match option_inner(&f.ty) {
+                    Some(_) => quote! {
+                        if #krate::__is_null(row, #name)? {
+                            ::std::option::Option::None
+                        } else {
+                            ::std::option::Option::Some(#get)
+                        }
+                    },
+                    None => get,
+                }
+            })
             .collect();
 
-        let del: String = primary_keys
-            .iter()
-            .enumerate()
-            .map(|(i, (f, _ty))| format!("{f} =${}", i + 1))
-            .intersperse(" AND ".to_string())
-            .collect();
+        let (owned, owned_struct) = if struct_generics.params.is_empty() {
+            (quote! { #struct_ident }, quote!())
+        } else {
+            let owned = format_ident!("{struct_ident}Owned");
+            let vis: Vec<&syn::Visibility> = fields.iter().map(|f| &f.vis).collect();
+            let serde_crate = quote!(#krate::serde).to_string();
+            (
+                quote! { #owned },
+                quote! {
+                    #[derive(Debug, Clone, PartialEq, #krate::serde::Serialize, #krate::serde::Deserialize)]
+                    #[serde(crate = #serde_crate)]
+                    #struct_vis struct #owned {
+                        #( #vis #idents: #tys, )*
+                    }
+                },
+            )
+        };
 
-        let pkey_constraint: String = primary_keys
-            .iter()
-            .map(|(f, _ty)| format!("{f}"))
-            .intersperse(", ".to_string())
-            .collect();
+        let from_row = quote! {
+            #owned_struct
 
-        let pg_insert = format!("INSERT INTO {pg_table_name} ( {select} ) VALUES ( {pg_values} )");
-        let sqlite_insert =
-            format!("INSERT INTO {sqlite_table_name} ( {select} ) VALUES ( {sqlite_values} )");
-        let pg_upsert = format!(
-            "{pg_insert} ON CONFLICT ON CONSTRAINT {constraint} DO UPDATE SET {upsert_set}"
-        );
-        let sqlite_upsert =
-            format!("{sqlite_insert} ON CONFLICT ({pkey_constraint}) DO UPDATE SET {upsert_set}");
-        let pg_delete = format!("DELETE FROM {pg_table_name} WHERE {del}");
-        let sqlite_delete = format!("DELETE FROM {sqlite_table_name} WHERE {del}");
-        (
-            pg_insert,
-            sqlite_insert,
-            pg_upsert,
-            sqlite_upsert,
-            pg_delete,
-            sqlite_delete,
-        )
+            #[automatically_derived]
+            impl<'r, R: #krate::sqlx::Row> #krate::sqlx::FromRow<'r, R> for #owned
+            where
+                &'static str: #krate::sqlx::ColumnIndex<R>,
+                #( #decode_tys: #krate::sqlx::Decode<'r, R::Database> + #krate::sqlx::Type<R::Database>, )*
+            {
+                fn from_row(row: &'r R) -> ::std::result::Result<Self, #krate::sqlx::Error> {
+                    ::std::result::Result::Ok(Self {
+                        #( #idents: #decodes, )*
+                    })
+                }
+            }
+        };
+        (owned, from_row)
     }
 
     fn expand(&self) -> proc_macro2::TokenStream {
         let Self {
+            krate,
+            struct_vis: _,
             struct_ident,
             struct_generics,
             schema_name: _,
             pg_table_name: _,
             sqlite_table_name: _,
+            mysql_table_name: _,
             primary_keys,
+            keys_in_row,
+            tuple_key: _,
+            drivers: _,
             constraint: _,
+            id: id_strategy,
             fields,
         } = self;
 
         let (_, ty_generics, where_clause) = struct_generics.split_for_impl();
 
-        let (pg_insert, sqlite_insert, pg_upsert, sqlite_upsert, pg_delete, sqlite_delete) =
-            self.gen_upsert();
+        let resource_name = struct_ident.to_string();
+        let (owned, owned_def) = self.gen_owned();
+        let (patch, patch_def) = self.gen_patch();
 
-        let mut primary_keys_c = primary_keys.clone();
-        // println!("pkeys: {primary_keys:#?}");
-        let (ids, ids_typ) = match primary_keys.len() {
-            0 => (quote! { () }, quote! { () }),
-            1 => {
-                let (id, typ) = primary_keys_c.remove(0);
-                (quote! { #id }, quote! { #typ })
-            }
-            _ => {
-                let (id1, typ1) = primary_keys_c.remove(0);
-                let ids: Vec<&syn::Ident> = primary_keys_c.iter().map(|(id, _typ)| id).collect();
-                let typs: Vec<&syn::Ident> = primary_keys_c.iter().map(|(_id, typ)| typ).collect();
-                (
-                    quote! { (#id1 #(, #ids)* ) },
-                    quote! { (#typ1 #(, #typs)* ) },
-                )
+        let pks: Vec<&syn::Ident> = primary_keys.iter().map(|k| &k.ident).collect();
+        let ids = self.key_bindings();
+        let ids_typ = match (self.key_struct(), primary_keys.as_slice()) {
+            (Some(key), _) => quote! { #key },
+            (None, [Field { ty, .. }]) => quote! { #ty },
+            (None, _) => {
+                let typs: Vec<&syn::Type> = primary_keys.iter().map(|k| &k.ty).collect();
+                quote! { ( #(#typs),* ) }
             }
         };
 
-        let bind_pks = match primary_keys.len() {
-            0 => quote!(),
-            _ => {
-                let pks: Vec<&syn::Ident> = primary_keys.iter().map(|(id, _typ)| id).collect();
-                quote! { #(.bind(&#pks))* }
-            }
-        };
+        let bind_pks = quote! { #(.bind(&#pks))* };
 
-        let bind_fields = match fields.len() {
-            0 => quote!(),
-            _ => {
-                let fs: Vec<&syn::Ident> = fields.iter().map(|f| &f.ident).collect();
-                quote! { #(.bind(&self.#fs))* }
+        let bind_fields = fields.iter().map(|f| {
+            let ident = &f.ident;
+            bind_value(krate, f, quote!(self.#ident))
+        });
+        let bind_fields = quote! { #(.bind(#bind_fields))* };
+
+        // Bound per row of a batch, from `__resource` and the key's columns.
+        let bind_row_pks = quote! { #(.bind(#pks))* };
+        let bind_row_fields = fields.iter().map(|f| {
+            let ident = &f.ident;
+            bind_value(krate, f, quote!(__resource.#ident))
+        });
+        let bind_row_fields = quote! { #(.bind(#bind_row_fields))* };
+        let copy_keys = primary_keys.iter().map(|k| {
+            let ident = &k.ident;
+            match is_bytes(&k.ty) {
+                true => quote! { row.bytes(&#ident)?; },
+                false => quote! { row.field(&#ident)?; },
+            }
+        });
+        let copy_fields = fields.iter().map(|f| {
+            let ident = &f.ident;
+            if f.json {
+                match option_inner(&f.ty) {
+                    Some(_) => quote! { row.json(self.#ident.as_ref())?; },
+                    None => quote! { row.json(Some(&self.#ident))?; },
+                }
+            } else if is_bytes(option_inner(&f.ty).unwrap_or(&f.ty)) {
+                quote! { row.bytes(&self.#ident)?; }
+            } else {
+                quote! { row.field(&self.#ident)?; }
+            }
+        });
+        let copy_row = quote! {
+            fn copy_row(
+                &self,
+                id: &Self::ResourceID,
+                row: &mut #krate::CopyRow,
+            ) -> Result<(), #krate::Error> {
+                let #ids = id.clone();
+                #( #copy_keys )*
+                #( #copy_fields )*
+                Ok(())
             }
         };
+        // `copy_in` has no executor to run `gen_id_with` on, so it asks for these.
+        let row_key = (*keys_in_row && id_strategy.is_none()).then(|| {
+            quote! {
+                fn row_key(&self) -> ::std::option::Option<Self::ResourceID> {
+                    #( let #pks = ::std::clone::Clone::clone(&self.#pks); )*
+                    ::std::option::Option::Some(#ids)
+                }
+            }
+        });
 
-        let impl_pg_res = quote! {
-            #[automatically_derived]
-            impl #ty_generics Resource<Postgres> for #struct_ident #ty_generics #where_clause {
-                type ResourceID = #ids_typ;
-                async fn insert<'c, E>(
-                    &self,
-                    id: &Option<Self::ResourceID>,
-                    exector: E,
-                ) -> Result<(), crate::Error>
-                where
-                    E: sqlx::Executor<'c, Database = Any>,
-                {
-                    let #ids = if let Some(#ids) = id.clone() {
-                        #ids
-                    } else {
-                        <Self as GenResourceID>::gen_id().await?
-                    };
+        // Keys the derive generates itself don't depend on the table, so a
+        // batch can generate them all before writing any row.
+        let keys_independent = *keys_in_row || id_strategy.is_some_and(|s| !s.assigned());
+        let gen_id = id_strategy.and_then(|strategy| {
+            let id = match strategy {
+                // Keyed by `SnowflakeID`'s blanket impl instead.
+                IdStrategy::Snowflake => return None,
+                IdStrategy::UuidV4 => quote!(#krate::id::uuid_v4()),
+                IdStrategy::UuidV7 => quote!(#krate::id::uuid_v7()),
+                // Inserts leave the key to the database instead.
+                IdStrategy::DbSequence | IdStrategy::AutoIncrement => {
+                    quote!(Err(#krate::Error::GenIdFailure))
+                }
+            };
+            let gen_id = if strategy.assigned() {
+                id
+            } else {
+                quote! {
+                    ::std::convert::TryFrom::try_from(#id).map_err(|_| #krate::Error::GenIdFailure)
+                }
+            };
+            Some(quote! {
+                async fn gen_id() -> Result<Self::Target, #krate::Error> {
+                    #gen_id
+                }
+            })
+        });
+        // Without a strategy, keys declared on fields key the row by its own values.
+        let gen_id = gen_id.or_else(|| {
+            (*keys_in_row && id_strategy.is_none()).then(|| {
+                quote! {
+                    async fn gen_id_with<'c, E>(&self, _executor: E) -> Result<Self::Target, #krate::Error>
+                    where
+                        E: #krate::ResourceExecutor<'c, Database = __D>,
+                    {
+                        #( let #pks = ::std::clone::Clone::clone(&self.#pks); )*
+                        Ok(#ids)
+                    }
+                }
+            })
+        });
+        let gen_id = gen_id.map(|gen_id| {
+            let mut generics = struct_generics.clone();
+            generics
+                .params
+                .push(syn::parse_quote!(__D: #krate::sqlx::Database));
+            let (impl_generics, _, where_clause) = generics.split_for_impl();
+            quote! {
+                #[automatically_derived]
+                impl #impl_generics #krate::GenResourceID<__D> for #struct_ident #ty_generics #where_clause {
+                    type Target = #ids_typ;
 
-                    sqlx::query(#pg_insert)
-                    #bind_pks
-                    #bind_fields
-                    .execute(exector)
-                    .await?;
-                Ok(())
+                    #gen_id
                 }
+            }
+        });
 
-                async fn upsert<'c, E>(&self, id: &Option<Self::ResourceID>, exector: E) -> Result<(), crate::Error>
-                where
-                    E: sqlx::Executor<'c, Database = Any>,
-                {
-                    let #ids = if let Some(#ids) = id.clone() {
-                        #ids
+        let impl_res = |db: Db, native: bool| {
+            let db_path = db.path(krate);
+            let driver = if native {
+                db_path.clone()
+            } else {
+                quote!(#krate::sqlx::Any)
+            };
+            let statements = Statements::new(db.dialect(), &self.table(db));
+            let patch_fn = self.gen_patch_fn(db, &statements, &driver);
+            let table_schema = self.gen_table_schema(db);
+            let Statements {
+                insert,
+                upsert,
+                update,
+                delete,
+                select,
+                exists,
+                insert_into,
+                values_row,
+                on_conflict,
+                batch_inserts,
+                batch_upserts,
+                insert_assigned,
+                create_table,
+                drop_table,
+                ..
+            } = statements;
+            // The database's key, read back from the row it inserted.
+            let assigned_key = insert_assigned.map(|sql| match db {
+                Db::MySql => {
+                    let last_insert_id = if native {
+                        quote!(res.last_insert_id())
                     } else {
-                        <Self as GenResourceID>::gen_id().await?
+                        quote!(res.last_insert_id().ok_or(#krate::Error::GenIdFailure)?)
                     };
-
-                    sqlx::query(#pg_upsert)
-                    #bind_pks
+                    quote! {
+                        let res = #krate::sqlx::query(#sql)
+                        #bind_fields
+                        .execute(exector)
+                        .await?;
+                        let #ids: #ids_typ = ::std::convert::TryFrom::try_from(#last_insert_id)
+                            .map_err(|_| #krate::Error::GenIdFailure)?;
+                    }
+                }
+                Db::Postgres | Db::Sqlite => quote! {
+                    let #ids: #ids_typ = #krate::sqlx::query_scalar(#sql)
                     #bind_fields
-                    .execute(exector)
+                    .fetch_one(exector)
                     .await?;
-                Ok(())
+                },
+            });
+            let gen_key = |kind: syn::Ident| match &assigned_key {
+                Some(assigned_key) => quote! {
+                    match id.clone() {
+                        Some(#ids) => #ids,
+                        None => {
+                            #assigned_key
+                            return Ok(#krate::ExecutionOutcome::new(#krate::ActionKind::#kind, 1, &#ids));
+                        }
+                    }
+                },
+                None => quote! {
+                    if let Some(#ids) = id.clone() {
+                        #ids
+                    } else {
+                        let exector = #krate::ResourceExecutor::reborrow(&mut exector);
+                        <Self as #krate::GenResourceID<#driver>>::gen_id_with(self, exector).await?
+                    }
+                },
+            };
+            // Generating the key borrows the executor the row is written with.
+            let rebind = assigned_key
+                .is_none()
+                .then(|| quote!(let mut exector = exector;));
+            let gen_insert_key = gen_key(format_ident!("Insert"));
+            let gen_upsert_key = gen_key(format_ident!("Upsert"));
+            let batch_fn = |name: syn::Ident, kind: syn::Ident, tail: &str| {
+                let method = format_ident!("{}", kind.to_string().to_lowercase());
+                // Writes `batch` keyed by `keys` in one statement.
+                let write = |exector: proc_macro2::TokenStream| {
+                    quote! {
+                        let sql = #krate::__values_sql(#insert_into, &[#( #values_row ),*], batch.len(), #tail);
+                        let mut query = #krate::sqlx::query(&sql);
+                        for ((__resource, _), __key) in batch.iter().zip(&keys) {
+                            let #ids = __key.clone();
+                            query = query #bind_row_pks #bind_row_fields;
+                        }
+                        query.execute(#exector).await?;
+                    }
+                };
+                let outcomes = quote! {
+                    keys.iter().map(|key| #krate::ExecutionOutcome::new(#krate::ActionKind::#kind, 1, key))
+                };
+                let body = if keys_independent {
+                    let write = write(quote!(exector));
+                    quote! {
+                        let mut exector = exector;
+                        let mut keys = Vec::with_capacity(rows.len());
+                        for (__resource, id) in rows {
+                            keys.push(match id {
+                                Some(id) => id.clone(),
+                                None => {
+                                    let exector = #krate::ResourceExecutor::reborrow(&mut exector);
+                                    <Self as #krate::GenResourceID<#driver>>::gen_id_with(__resource, exector).await?
+                                }
+                            });
+                        }
+                        let batch = rows;
+                        #write
+                        Ok(#outcomes.collect())
+                    }
+                } else {
+                    let write = write(quote!(#krate::ResourceExecutor::reborrow(&mut exector)));
+                    quote! {
+                        let mut exector = exector;
+                        let mut outcomes = Vec::with_capacity(rows.len());
+                        let mut rest = rows;
+                        while !rest.is_empty() {
+                            // Its key may come from the table, or from the
+                            // database, so it's written after the rows before it.
+                            if let [(__resource, None), tail @ ..] = rest {
+                                let exector = #krate::ResourceExecutor::reborrow(&mut exector);
+                                outcomes.push(
+                                    <Self as #krate::Resource<#db_path, #driver>>::#method(__resource, &None, exector).await?,
+                                );
+                                rest = tail;
+                                continue;
+                            }
+                            let len = rest.iter().position(|(_, id)| id.is_none()).unwrap_or(rest.len());
+                            let (batch, tail) = rest.split_at(len);
+                            let keys: Vec<Self::ResourceID> =
+                                batch.iter().filter_map(|(_, id)| (*id).clone()).collect();
+                            #write
+                            outcomes.extend(#outcomes);
+                            rest = tail;
+                        }
+                        Ok(outcomes)
+                    }
+                };
+                quote! {
+                    async fn #name<'c, E>(
+                        rows: &[(&Self, &Option<Self::ResourceID>)],
+                        exector: E,
+                    ) -> Result<Vec<#krate::ExecutionOutcome>, #krate::Error>
+                    where
+                        E: #krate::ResourceExecutor<'c, Database = #driver>,
+                    {
+                        #krate::ResourceExecutor::ensure_backend::<#db_path>(&exector)?;
+                        #body
+                    }
+                }
+            };
+            let insert_batch = batch_fn(format_ident!("insert_batch"), format_ident!("Insert"), "");
+            let upsert_batch = batch_fn(
+                format_ident!("upsert_batch"),
+                format_ident!("Upsert"),
+                &on_conflict,
+            );
+            let bind_update = if db.dialect().positional() {
+                quote! { #bind_fields #bind_pks }
+            } else {
+                quote! { #bind_pks #bind_fields }
+            };
+            let db = db.path(krate);
+            // Every database has a native impl; its types come with that one.
+            let types = native.then(|| {
+                quote! {
+                    #[automatically_derived]
+                    impl #ty_generics #krate::ResourceTypes<#db> for #struct_ident #ty_generics #where_clause {
+                        type ResourceID = #ids_typ;
+                        type Owned = #owned;
+                        type Patch = #patch;
+                    }
                 }
+            });
+            quote! {
+                #types
 
-                async fn update<'c, E>(&self, id: &Self::ResourceID, exector: E) -> Result<(), crate::Error>
-                where
-                E: sqlx::Executor<'c, Database = Any>,
-                {
-                    let #ids = id.clone();
+                #[automatically_derived]
+                impl #ty_generics #krate::Resource<#db, #driver> for #struct_ident #ty_generics #where_clause {
+                    fn create_table_sql() -> &'static str {
+                        #create_table
+                    }
 
-                    sqlx::query(#pg_upsert)
-                    #bind_pks
-                    #bind_fields
-                    .execute(exector)
-                    .await?;
-                Ok(())
-                }
+                    fn drop_table_sql() -> &'static str {
+                        #drop_table
+                    }
 
-                async fn drop<'c, E>(id: &Self::ResourceID, exector: E) -> Result<(), crate::Error>
-                where
-                E: sqlx::Executor<'c, Database = Any>,
-                {
-                    let #ids = id.clone();
+                    fn table_schema() -> #krate::TableSchema {
+                        #table_schema
+                    }
 
-                    sqlx::query(#pg_delete)
-                    #bind_pks
-                    .execute(exector)
-                    .await?;
-                Ok(())
+                    fn batch_rows(kind: #krate::ActionKind) -> usize {
+                        match kind {
+                            #krate::ActionKind::Insert => #batch_inserts,
+                            #krate::ActionKind::Upsert => #batch_upserts,
+                            _ => 1,
+                        }
+                    }
+
+                    #insert_batch
+
+                    #upsert_batch
+
+                    #copy_row
+
+                    #row_key
+
+                    async fn insert<'c, E>(
+                        &self,
+                        id: &Option<Self::ResourceID>,
+                        exector: E,
+                    ) -> Result<#krate::ExecutionOutcome, #krate::Error>
+                    where
+                        E: #krate::ResourceExecutor<'c, Database = #driver>,
+                    {
+                        #krate::ResourceExecutor::ensure_backend::<#db>(&exector)?;
+                        #rebind
+                        let #ids = #gen_insert_key;
+
+                        let res = #krate::sqlx::query(#insert)
+                        #bind_pks
+                        #bind_fields
+                        .execute(exector)
+                        .await?;
+                    Ok(#krate::ExecutionOutcome::new(#krate::ActionKind::Insert, res.rows_affected(), &#ids))
+                    }
+
+                    async fn upsert<'c, E>(&self, id: &Option<Self::ResourceID>, exector: E) -> Result<#krate::ExecutionOutcome, #krate::Error>
+                    where
+                        E: #krate::ResourceExecutor<'c, Database = #driver>,
+                    {
+                        #krate::ResourceExecutor::ensure_backend::<#db>(&exector)?;
+                        #rebind
+                        let #ids = #gen_upsert_key;
+
+                        let res = #krate::sqlx::query(#upsert)
+                        #bind_pks
+                        #bind_fields
+                        .execute(exector)
+                        .await?;
+                    Ok(#krate::ExecutionOutcome::new(#krate::ActionKind::Upsert, res.rows_affected(), &#ids))
+                    }
+
+                    async fn update<'c, E>(&self, id: &Self::ResourceID, exector: E) -> Result<#krate::ExecutionOutcome, #krate::Error>
+                    where
+                    E: #krate::ResourceExecutor<'c, Database = #driver>,
+                    {
+                        #krate::ResourceExecutor::ensure_backend::<#db>(&exector)?;
+                        let #ids = id.clone();
+
+                        let res = #krate::sqlx::query(#update)
+                        #bind_update
+                        .execute(exector)
+                        .await?;
+                        if res.rows_affected() == 0 {
+                            return Err(#krate::Error::not_found(#resource_name, &#ids));
+                        }
+                    Ok(#krate::ExecutionOutcome::new(#krate::ActionKind::Update, res.rows_affected(), &#ids))
+                    }
+
+                    async fn drop<'c, E>(id: &Self::ResourceID, exector: E) -> Result<#krate::ExecutionOutcome, #krate::Error>
+                    where
+                    E: #krate::ResourceExecutor<'c, Database = #driver>,
+                    {
+                        #krate::ResourceExecutor::ensure_backend::<#db>(&exector)?;
+                        let #ids = id.clone();
+
+                        let res = #krate::sqlx::query(#delete)
+                        #bind_pks
+                        .execute(exector)
+                        .await?;
+                    Ok(#krate::ExecutionOutcome::new(#krate::ActionKind::Drop, res.rows_affected(), &#ids))
+                    }
+
+                    #patch_fn
+
+                    async fn fetch_by_id<'c, E>(id: &Self::ResourceID, exector: E) -> Result<Self::Owned, #krate::Error>
+                    where
+                    E: #krate::ResourceExecutor<'c, Database = #driver>,
+                    {
+                        #krate::ResourceExecutor::ensure_backend::<#db>(&exector)?;
+                        let #ids = id.clone();
+
+                        let owned = #krate::sqlx::query_as(#select)
+                        #bind_pks
+                        .fetch_optional(exector)
+                        .await?;
+                        owned.ok_or_else(|| #krate::Error::not_found(#resource_name, &#ids))
+                    }
+
+                    async fn fetch_optional_by_id<'c, E>(
+                        id: &Self::ResourceID,
+                        exector: E,
+                    ) -> Result<Option<Self::Owned>, #krate::Error>
+                    where
+                    E: #krate::ResourceExecutor<'c, Database = #driver>,
+                    {
+                        #krate::ResourceExecutor::ensure_backend::<#db>(&exector)?;
+                        let #ids = id.clone();
+
+                        let owned = #krate::sqlx::query_as(#select)
+                        #bind_pks
+                        .fetch_optional(exector)
+                        .await?;
+                    Ok(owned)
+                    }
+
+                    async fn exists<'c, E>(id: &Self::ResourceID, exector: E) -> Result<bool, #krate::Error>
+                    where
+                    E: #krate::ResourceExecutor<'c, Database = #driver>,
+                    {
+                        #krate::ResourceExecutor::ensure_backend::<#db>(&exector)?;
+                        let #ids = id.clone();
+
+                        let row = #krate::sqlx::query(#exists)
+                        #bind_pks
+                        .fetch_optional(exector)
+                        .await?;
+                    Ok(row.is_some())
+                    }
                 }
             }
         };
 
-        let impl_sqlite_res = quote! {
-            #[automatically_derived]
-            impl #ty_generics Resource<Sqlite> for #struct_ident #ty_generics #where_clause {
-                type ResourceID = #ids_typ;
-                async fn insert<'c, E>(&self, id: &Option<Self::ResourceID>, exector: E) -> Result<(), crate::Error>
-                where
-                E: sqlx::Executor<'c, Database = Any>,
-                {
-                    let #ids = if let Some(#ids) = id.clone() {
-                        #ids
-                    } else {
-                        <Self as GenResourceID>::gen_id().await?
-                    };
-
-                    sqlx::query(#sqlite_insert)
-                    #bind_pks
-                    #bind_fields
-                    .execute(exector)
-                    .await?;
-                Ok(())
-                }
+        let mut expanded = proc_macro2::TokenStream::from_iter([
+            owned_def,
+            patch_def,
+            self.gen_key_struct(),
+            gen_id.unwrap_or_default(),
+        ]);
+        expanded.extend(
+            self.drivers
+                .iter()
+                .map(|&(db, native)| impl_res(db, native)),
+        );
+        expanded
+    }
+}
 
-                async fn upsert<'c, E>(&self, id: &Option<Self::ResourceID>, exector: E) -> Result<(), crate::Error>
-                where
-                E: sqlx::Executor<'c, Database = Any>,
-                {
-                    let #ids = if let Some(#ids) = id.clone() {
-                        #ids
-                    } else {
-                        <Self as GenResourceID>::gen_id().await?
-                    };
+/// A field's value as bound to a statement: a reference to it, or its JSON
+/// text for `#[resource(json)]` fields, where `None` binds `NULL`.
+fn bind_value(
+    krate: &syn::Path,
+    field: &Field,
+    value: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    match (field.json, option_inner(&field.ty)) {
+        (false, _) => quote!(&#value),
+        (true, None) => quote!(#krate::__to_json(&#value)?),
+        (true, Some(_)) => quote! {
+            ::std::option::Option::as_ref(&#value).map(#krate::__to_json).transpose()?
+        },
+    }
+}
 
-                    sqlx::query(#sqlite_upsert)
-                    #bind_pks
-                    #bind_fields
-                    .execute(exector)
-                    .await?;
-                Ok(())
+fn owned_type(ty: &syn::Type) -> syn::Type {
+    match ty {
+        syn::Type::Reference(r) => match &*r.elem {
+            syn::Type::Path(p) if p.path.is_ident("str") => {
+                syn::parse_quote!(::std::string::String)
+            }
+            elem => syn::parse_quote!(<#elem as ::std::borrow::ToOwned>::Owned),
+        },
+        syn::Type::Path(p) => {
+            let mut p = p.clone();
+            for seg in p.path.segments.iter_mut() {
+                if let syn::PathArguments::AngleBracketed(args) = &mut seg.arguments {
+                    for arg in args.args.iter_mut() {
+                        if let syn::GenericArgument::Type(t) = arg {
+                            *t = owned_type(t);
+                        }
+                    }
                 }
+            }
+            syn::Type::Path(p)
+        }
+        _ => ty.clone(),
+    }
+}
 
-                async fn update<'c, E>(&self, id: &Self::ResourceID, exector: E) -> Result<(), crate::Error>
-                where
-                E: sqlx::Executor<'c, Database = Any>,
-                {
-                    let #ids = id.clone();
+fn has_lifetime(ty: &syn::Type) -> bool {
+    fn walk(tokens: proc_macro2::TokenStream) -> bool {
+        tokens.into_iter().any(|tt| match tt {
+            proc_macro2::TokenTree::Punct(p) => p.as_char() == '\'',
+            proc_macro2::TokenTree::Group(g) => walk(g.stream()),
+            _ => false,
+        })
+    }
+    walk(quote!(#ty))
+}
 
-                    sqlx::query(#sqlite_upsert)
-                    #bind_pks
-                    #bind_fields
-                    .execute(exector)
-                    .await?;
-                Ok(())
-                }
+fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(p) = ty else {
+        return None;
+    };
+    let seg = p.path.segments.last()?;
+    if seg.ident != "Option" {
+        return None;
+    }
+    match &seg.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(syn::GenericArgument::Type(t)) => Some(t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
 
-                async fn drop<'c, E>(id: &Self::ResourceID, exector: E) -> Result<(), crate::Error>
-                where
-                E: sqlx::Executor<'c, Database = Any>,
-                {
-                    let #ids = id.clone();
+/// `&[u8]`, `[u8; N]` or `Vec<u8>`.
+fn is_bytes(ty: &syn::Type) -> bool {
+    let elem = match ty {
+        syn::Type::Array(a) => &*a.elem,
+        syn::Type::Reference(r) => match &*r.elem {
+            syn::Type::Slice(s) => &*s.elem,
+            _ => return false,
+        },
+        syn::Type::Path(p) => match p.path.segments.last() {
+            Some(seg) if seg.ident == "Vec" => match &seg.arguments {
+                syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(syn::GenericArgument::Type(t)) => t,
+                    _ => return false,
+                },
+                _ => return false,
+            },
+            _ => return false,
+        },
+        _ => return false,
+    };
+    matches!(elem, syn::Type::Path(p) if p.path.is_ident("u8"))
+}
 
-                    sqlx::query(#sqlite_delete)
-                    #bind_pks
-                    .execute(exector)
-                    .await?;
-                Ok(())
-                }
+fn is_text(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(r) => is_text(&r.elem),
+        syn::Type::Path(p) => match p.path.segments.last() {
+            Some(seg) if seg.ident == "str" || seg.ident == "String" => true,
+            _ => option_inner(ty).is_some_and(is_text),
+        },
+        _ => false,
+    }
+}
+
+/// `drivers = "..."`: the databases named, each through its native driver,
+/// and through `Any` as well if `any` is named. All of them by default.
+fn parse_drivers(lit: Option<&syn::Lit>) -> syn::Result<Vec<(Db, bool)>> {
+    let Some(lit) = lit else {
+        let mut dbs = vec![Db::Postgres, Db::Sqlite];
+        if cfg!(feature = "mysql") {
+            dbs.push(Db::MySql);
+        }
+        return Ok(dbs
+            .into_iter()
+            .flat_map(|db| [(db, false), (db, true)])
+            .collect());
+    };
+    let syn::Lit::Str(lit_str) = lit else {
+        return Err(syn::Error::new_spanned(lit, "attribute must be a string"));
+    };
+    let mut any = false;
+    let mut dbs = Vec::new();
+    for name in lit_str.value().split(',').map(str::trim) {
+        let db = match name {
+            "any" => {
+                any = true;
+                continue;
+            }
+            "postgres" => Db::Postgres,
+            "sqlite" => Db::Sqlite,
+            "mysql" if cfg!(feature = "mysql") => Db::MySql,
+            "mysql" => {
+                return Err(syn::Error::new_spanned(
+                    lit,
+                    "`mysql` needs the `mysql` feature",
+                ));
+            }
+            _ => {
+                let msg = format!(
+                    "unknown driver `{name}`, expected `any`, `postgres`, `sqlite` or `mysql`"
+                );
+                return Err(syn::Error::new_spanned(lit, msg));
             }
         };
+        if !dbs.contains(&db) {
+            dbs.push(db);
+        }
+    }
+    if dbs.is_empty() {
+        return Err(syn::Error::new_spanned(
+            lit,
+            "`drivers` needs a database: `postgres`, `sqlite` or `mysql`",
+        ));
+    }
+    Ok(dbs
+        .into_iter()
+        .flat_map(|db| [(db, false), (db, true)])
+        .filter(|&(_, native)| native || any)
+        .collect())
+}
+
+/// `"name: Type, ..."`, where each type is any Rust type.
+fn parse_primary_key(lit: &syn::Lit) -> syn::Result<Vec<Field>> {
+    use syn::parse::ParseStream;
 
-        proc_macro2::TokenStream::from_iter([impl_pg_res, impl_sqlite_res])
-        // impl_pg_res
+    let syn::Lit::Str(lit_str) = lit else {
+        return Err(syn::Error::new_spanned(lit, "attribute must be a string"));
+    };
+    let keys = lit_str.parse_with(|input: ParseStream| {
+        syn::punctuated::Punctuated::<_, syn::Token![,]>::parse_terminated_with(
+            input,
+            |input: ParseStream| {
+                let ident: syn::Ident = input.parse()?;
+                input.parse::<syn::Token![:]>()?;
+                let ty: syn::Type = input.parse()?;
+                Ok((ident, ty))
+            },
+        )
+    })?;
+    if keys.is_empty() {
+        return Err(syn::Error::new_spanned(lit, "expected `name: Type` keys"));
     }
+    Ok(keys
+        .into_iter()
+        .map(|(ident, ty)| Field {
+            name: trim_starting_raw_identifier(&ident),
+            typ: None,
+            json: false,
+            vis: syn::Visibility::Inherited,
+            ident,
+            ty,
+        })
+        .collect())
 }
 
-fn parse_lit_string(lit: &syn::Lit) -> syn::Result<TokenStream> {
+fn parse_lit_string(lit: &syn::Lit) -> syn::Result<proc_macro2::TokenStream> {
     match lit {
         syn::Lit::Str(lit_str) => lit_str
             .value()
@@ -421,3 +1375,123 @@ where
         .trim_start_matches(RAW_IDENTIFIER)
         .to_string()
 }
+
+#[cfg(test)]
+mod test {
+    use super::{parse_drivers, Db, DeriveResource};
+
+    fn error(input: syn::DeriveInput) -> String {
+        DeriveResource::new(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn key_errors() {
+        assert_eq!(
+            error(syn::parse_quote! {
+                #[resource(pg_table_name = "t", sqlite_table_name = "t", constraint = "t_pkey", primary_key = "id i64")]
+                struct T {}
+            }),
+            "expected `:`"
+        );
+        assert_eq!(
+            error(syn::parse_quote! {
+                #[resource(pg_table_name = "t", sqlite_table_name = "t", constraint = "t_pkey", primary_key = "id: i64,, gid: i64")]
+                struct T {}
+            }),
+            "expected identifier"
+        );
+        assert_eq!(
+            error(syn::parse_quote! {
+                #[resource(pg_table_name = "t", sqlite_table_name = "t", constraint = "t_pkey")]
+                struct T {
+                    a: i64,
+                }
+            }),
+            "missing key: add `primary_key = \"id: i64\"` or mark fields with `#[resource(primary_key)]`"
+        );
+        assert_eq!(
+            error(syn::parse_quote! {
+                #[resource(pg_table_name = "t", sqlite_table_name = "t", constraint = "t_pkey", primary_key = "id: i64")]
+                struct T {
+                    #[resource(primary_key)]
+                    a: i64,
+                }
+            }),
+            "the key is already declared with `#[resource(primary_key)]` on fields"
+        );
+        assert_eq!(
+            error(syn::parse_quote! {
+                #[resource(pg_table_name = "t", sqlite_table_name = "t", constraint = "t_pkey")]
+                struct T {
+                    #[resource(primary_key, typ = "uuid")]
+                    a: String,
+                }
+            }),
+            "`typ` isn't supported on key columns"
+        );
+        assert_eq!(
+            error(syn::parse_quote! {
+                #[resource(pg_table_name = "t", sqlite_table_name = "t", constraint = "t_pkey")]
+                struct T {
+                    #[resource(primary_key, json)]
+                    a: Vec<i64>,
+                }
+            }),
+            "`json` isn't supported on key columns"
+        );
+        assert_eq!(
+            error(syn::parse_quote! {
+                #[resource(pg_table_name = "t", sqlite_table_name = "t", constraint = "t_pkey", primary_key = "id: i64, name: &'a str")]
+                struct T<'a> {
+                    a: &'a str,
+                }
+            }),
+            "`<Name>Key` can't borrow; use owned key types or `#[resource(tuple_key)]`"
+        );
+    }
+
+    #[test]
+    fn key_types() {
+        let derive = DeriveResource::new(syn::parse_quote! {
+            #[resource(pg_table_name = "t", sqlite_table_name = "t", constraint = "t_pkey", primary_key = "id: uuid::Uuid, hash: [u8; 16]")]
+            struct T {}
+        })
+        .unwrap();
+        let keys: Vec<String> = derive
+            .primary_keys
+            .iter()
+            .map(|k| {
+                let ty = &k.ty;
+                format!("{}: {}", k.name, quote::quote!(#ty))
+            })
+            .collect();
+        assert_eq!(keys, ["id: uuid :: Uuid", "hash: [u8 ; 16]"]);
+    }
+
+    #[test]
+    fn drivers() {
+        let drivers = |s: &str| parse_drivers(Some(&syn::parse_quote!(#s)));
+        assert_eq!(drivers("postgres").unwrap(), [(Db::Postgres, true)]);
+        assert_eq!(
+            drivers("sqlite, any,postgres").unwrap(),
+            [
+                (Db::Sqlite, false),
+                (Db::Sqlite, true),
+                (Db::Postgres, false),
+                (Db::Postgres, true)
+            ]
+        );
+        assert_eq!(
+            parse_drivers(None).unwrap()[..4],
+            drivers("any, postgres, sqlite").unwrap()
+        );
+        assert_eq!(
+            drivers("any").unwrap_err().to_string(),
+            "`drivers` needs a database: `postgres`, `sqlite` or `mysql`"
+        );
+        assert_eq!(
+            drivers("pg").unwrap_err().to_string(),
+            "unknown driver `pg`, expected `any`, `postgres`, `sqlite` or `mysql`"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use heck::ToSnakeCase as _;
+use quote::{format_ident, quote};
+
+use crate::attributes;
+
+struct Variant {
+    ident: syn::Ident,
+    ty: syn::Type,
+}
+
+pub(crate) struct DeriveResources {
+    krate: syn::Path,
+    enum_ident: syn::Ident,
+    enum_generics: syn::Generics,
+    variants: Vec<Variant>,
+}
+
+impl DeriveResources {
+    pub(crate) fn new(input: syn::DeriveInput) -> syn::Result<Self> {
+        let (krate, _) = attributes::take_crate_path(&input.attrs)?;
+        let krate = krate.unwrap_or_else(|| syn::parse_quote!(::resource));
+
+        let syn::Data::Enum(data) = input.data else {
+            return Err(syn::Error::new_spanned(
+                input.ident,
+                "`Resources` can only be derived for enums",
+            ));
+        };
+        let variants = data
+            .variants
+            .into_iter()
+            .map(|variant| match variant.fields {
+                syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Ok(Variant {
+                    ident: variant.ident,
+                    ty: fields.unnamed.into_iter().next().unwrap().ty,
+                }),
+                _ => Err(syn::Error::new_spanned(
+                    variant,
+                    "`Resources` variants must wrap exactly one command",
+                )),
+            })
+            .collect::<syn::Result<_>>()?;
+
+        Ok(DeriveResources {
+            krate,
+            enum_ident: input.ident,
+            enum_generics: input.generics,
+            variants,
+        })
+    }
+
+    pub(crate) fn expand(&self) -> proc_macro2::TokenStream {
+        let Self {
+            krate,
+            enum_ident,
+            enum_generics,
+            variants,
+        } = self;
+
+        let (impl_generics, ty_generics, where_clause) = enum_generics.split_for_impl();
+        let idents: Vec<&syn::Ident> = variants.iter().map(|v| &v.ident).collect();
+        let tys: Vec<&syn::Type> = variants.iter().map(|v| &v.ty).collect();
+
+        // Implemented for every driver all payloads can run on.
+        let mut action_generics = enum_generics.clone();
+        action_generics
+            .params
+            .push(syn::parse_quote!(__D: #krate::sqlx::Database));
+        let predicates = action_generics.make_where_clause();
+        for Variant { ty, .. } in variants {
+            predicates
+                .predicates
+                .push(syn::parse_quote!(#ty: #krate::Action<__D>));
+        }
+        let (action_impl_generics, _, action_where_clause) = action_generics.split_for_impl();
+
+        let impl_action = quote! {
+            #[automatically_derived]
+            impl #impl_generics #krate::Resources for #enum_ident #ty_generics #where_clause {}
+
+            #[automatically_derived]
+            impl #action_impl_generics #krate::Action<__D> for #enum_ident #ty_generics #action_where_clause {
+                async fn execute<'c, E>(
+                    &self,
+                    executor: E,
+                ) -> Result<#krate::ExecutionOutcome, #krate::Error>
+                where
+                    E: #krate::ResourceExecutor<'c, Database = __D>,
+                {
+                    match self {
+                        #( Self::#idents(r) => #krate::Action::<__D>::execute(r, executor).await, )*
+                    }
+                }
+
+                // Only a run of the first action's variant can batch.
+                #[allow(unreachable_patterns)]
+                fn batch_len(actions: &[&Self]) -> usize {
+                    match actions.first() {
+                        #(
+                            Some(Self::#idents(_)) => {
+                                let run: Vec<&#tys> = actions
+                                    .iter()
+                                    .map_while(|a| match a {
+                                        Self::#idents(r) => Some(r),
+                                        _ => None,
+                                    })
+                                    .collect();
+                                <#tys as #krate::Action<__D>>::batch_len(&run)
+                            }
+                        )*
+                        None => 0,
+                    }
+                }
+
+                // Each run of one variant goes to that variant's payload.
+                #[allow(unreachable_patterns)]
+                async fn execute_batch<'c, E>(
+                    actions: &[&Self],
+                    mut executor: E,
+                ) -> Result<Vec<#krate::ExecutionOutcome>, #krate::Error>
+                where
+                    E: #krate::ResourceExecutor<'c, Database = __D>,
+                {
+                    let mut outcomes = Vec::with_capacity(actions.len());
+                    let mut rest = actions;
+                    while let Some(first) = rest.first() {
+                        let executor = #krate::ResourceExecutor::reborrow(&mut executor);
+                        let run = match first {
+                            #(
+                                Self::#idents(_) => {
+                                    let run: Vec<&#tys> = rest
+                                        .iter()
+                                        .map_while(|a| match a {
+                                            Self::#idents(r) => Some(r),
+                                            _ => None,
+                                        })
+                                        .collect();
+                                    outcomes.extend(
+                                        <#tys as #krate::Action<__D>>::execute_batch(&run, executor).await?,
+                                    );
+                                    run.len()
+                                }
+                            )*
+                        };
+                        rest = &rest[run..];
+                    }
+                    Ok(outcomes)
+                }
+            }
+
+            #[automatically_derived]
+            impl #impl_generics #krate::Trace for #enum_ident #ty_generics #where_clause {
+                fn trace(&self) -> Option<(i64, &str)> {
+                    match self {
+                        #( Self::#idents(r) => #krate::Trace::trace(r), )*
+                    }
+                }
+            }
+        };
+
+        // `From` would be ambiguous for variants sharing a payload type.
+        let type_key = |ty: &syn::Type| quote!(#ty).to_string();
+        let impl_from = variants
+            .iter()
+            .filter(|v| {
+                variants
+                    .iter()
+                    .filter(|other| type_key(&other.ty) == type_key(&v.ty))
+                    .count()
+                    == 1
+            })
+            .map(|Variant { ident, ty }| {
+                quote! {
+                    #[automatically_derived]
+                    impl #impl_generics ::std::convert::From<#ty> for #enum_ident #ty_generics #where_clause {
+                        fn from(value: #ty) -> Self {
+                            Self::#ident(value)
+                        }
+                    }
+                }
+            });
+
+        let constructors = variants.iter().filter_map(|Variant { ident, ty }| {
+            let action = command_action(ty)?;
+            let ctor = format_ident!("{}", ident.to_string().to_snake_case());
+            Some(quote! {
+                pub fn #ctor(trace: i64, action: #action, tag: impl Into<String>) -> Self {
+                    Self::#ident(#krate::Command::new(trace, action, tag.into()))
+                }
+            })
+        });
+
+        quote! {
+            #impl_action
+
+            #( #impl_from )*
+
+            #[automatically_derived]
+            impl #impl_generics #enum_ident #ty_generics #where_clause {
+                #( #constructors )*
+            }
+        }
+    }
+}
+
+/// `A` if the variant wraps a `Command<A>`.
+fn command_action(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(p) = ty else {
+        return None;
+    };
+    let seg = p.path.segments.last()?;
+    if seg.ident != "Command" {
+        return None;
+    }
+    match &seg.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(syn::GenericArgument::Type(t)) => Some(t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,384 @@
+use serde::Serialize;
+use sqlx::{
+    database::Database as SqlxDatabase,
+    encode::{Encode, IsNull},
+    postgres::{PgArgumentBuffer, PgConnection, PgCopyIn},
+    PgPool, Postgres,
+};
+
+use crate::{GenResourceID, Resource, TableSchema};
+
+/// Rows are sent to the server whenever this much data is buffered.
+const FLUSH_AT: usize = 1 << 20;
+
+/// Merged into the target table, then dropped with the transaction.
+const STAGING: &str = "\"resource_copy_staging\"";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyFormat {
+    /// Values as Postgres parses them from text, so `typ` columns take the
+    /// same strings a cast would.
+    #[default]
+    Text,
+    /// Values as sqlx binds them. Faster, but every Rust type must match
+    /// its column type exactly.
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopyOptions {
+    pub format: CopyFormat,
+    /// Overwrite existing rows instead of failing on their keys.
+    pub upsert: bool,
+}
+
+impl CopyOptions {
+    pub fn binary(mut self) -> Self {
+        self.format = CopyFormat::Binary;
+        self
+    }
+
+    pub fn upsert(mut self) -> Self {
+        self.upsert = true;
+        self
+    }
+}
+
+/// `COPY` data being written, one [`Resource::copy_row`] at a time.
+pub struct CopyRow {
+    format: CopyFormat,
+    data: Vec<u8>,
+    scratch: PgArgumentBuffer,
+    first: bool,
+}
+
+impl CopyRow {
+    pub(crate) fn new(format: CopyFormat) -> Self {
+        let mut data = Vec::new();
+        if format == CopyFormat::Binary {
+            data.extend_from_slice(b"PGCOPY\n\xff\r\n\0");
+            // Flags, then the length of the header extension.
+            data.extend_from_slice(&0i32.to_be_bytes());
+            data.extend_from_slice(&0i32.to_be_bytes());
+        }
+        CopyRow {
+            format,
+            data,
+            scratch: PgArgumentBuffer::default(),
+            first: true,
+        }
+    }
+
+    pub(crate) fn begin(&mut self, columns: usize) {
+        if self.format == CopyFormat::Binary {
+            self.data.extend_from_slice(&(columns as i16).to_be_bytes());
+        }
+        self.first = true;
+    }
+
+    pub(crate) fn end(&mut self) {
+        if self.format == CopyFormat::Text {
+            self.data.push(b'\n');
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        if self.format == CopyFormat::Binary {
+            self.data.extend_from_slice(&(-1i16).to_be_bytes());
+        }
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.data)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn field<T>(&mut self, value: &T) -> Result<(), crate::Error>
+    where
+        T: Serialize + for<'q> Encode<'q, Postgres>,
+    {
+        self.write(value, false)
+    }
+
+    /// A `BYTEA` column, which text `COPY` takes hex encoded.
+    pub fn bytes<T>(&mut self, value: &T) -> Result<(), crate::Error>
+    where
+        T: Serialize + for<'q> Encode<'q, Postgres>,
+    {
+        self.write(value, true)
+    }
+
+    /// A `#[resource(json)]` column: the value's JSON text, `NULL` for `None`.
+    pub fn json<T>(&mut self, value: Option<&T>) -> Result<(), crate::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let json = value.map(crate::__to_json).transpose()?;
+        match (self.format, json) {
+            // `jsonb`'s binary format is a version byte, then the text.
+            (CopyFormat::Binary, Some(json)) => {
+                self.first = false;
+                self.data
+                    .extend_from_slice(&(json.len() as i32 + 1).to_be_bytes());
+                self.data.push(1);
+                self.data.extend_from_slice(json.as_bytes());
+                Ok(())
+            }
+            (_, json) => self.write(&json, false),
+        }
+    }
+
+    fn write<T>(&mut self, value: &T, bytes: bool) -> Result<(), crate::Error>
+    where
+        T: Serialize + for<'q> Encode<'q, Postgres>,
+    {
+        let first = std::mem::replace(&mut self.first, false);
+        match self.format {
+            CopyFormat::Binary => {
+                self.scratch.clear();
+                match value.encode_by_ref(&mut self.scratch) {
+                    IsNull::Yes => self.data.extend_from_slice(&(-1i32).to_be_bytes()),
+                    IsNull::No => {
+                        let len = self.scratch.len() as i32;
+                        self.data.extend_from_slice(&len.to_be_bytes());
+                        self.data.extend_from_slice(&self.scratch);
+                    }
+                }
+            }
+            CopyFormat::Text => {
+                if !first {
+                    self.data.push(b'\t');
+                }
+                let json = serde_json::to_value(value).map_err(crate::Error::Encode)?;
+                self.scratch.clear();
+                // JSON has no NaN or infinities, so `serde_json` makes them `null`.
+                match json {
+                    serde_json::Value::Null
+                        if matches!(value.encode_by_ref(&mut self.scratch), IsNull::Yes) =>
+                    {
+                        self.data.extend_from_slice(b"\\N")
+                    }
+                    serde_json::Value::Null => self.write_float()?,
+                    json => self.write_text(json, bytes),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The non-finite float in `scratch`, as Postgres spells it.
+    fn write_float(&mut self) -> Result<(), crate::Error> {
+        let float = match self.scratch[..] {
+            [a, b, c, d] => f32::from_be_bytes([a, b, c, d]) as f64,
+            [a, b, c, d, e, f, g, h] => f64::from_be_bytes([a, b, c, d, e, f, g, h]),
+            _ => {
+                let msg = "a non-null value serialized as `null`";
+                return Err(crate::Error::Encode(serde::ser::Error::custom(msg)));
+            }
+        };
+        let text: &[u8] = match float {
+            f if f.is_nan() => b"NaN",
+            f if f > 0.0 => b"Infinity",
+            _ => b"-Infinity",
+        };
+        self.data.extend_from_slice(text);
+        Ok(())
+    }
+
+    fn write_text(&mut self, value: serde_json::Value, bytes: bool) {
+        use serde_json::Value;
+
+        match value {
+            Value::Null => self.data.extend_from_slice(b"\\N"),
+            Value::Bool(b) => self.data.push(if b { b't' } else { b'f' }),
+            Value::Number(n) => self.data.extend_from_slice(n.to_string().as_bytes()),
+            Value::String(s) => self.escape(&s),
+            Value::Array(octets) if bytes => {
+                self.data.extend_from_slice(b"\\\\x");
+                for octet in octets {
+                    let octet = octet.as_u64().unwrap_or_default();
+                    self.data
+                        .extend_from_slice(format!("{octet:02x}").as_bytes());
+                }
+            }
+            json => self.escape(&json.to_string()),
+        }
+    }
+
+    fn escape(&mut self, s: &str) {
+        for b in s.bytes() {
+            match b {
+                b'\\' => self.data.extend_from_slice(b"\\\\"),
+                b'\n' => self.data.extend_from_slice(b"\\n"),
+                b'\r' => self.data.extend_from_slice(b"\\r"),
+                b'\t' => self.data.extend_from_slice(b"\\t"),
+                b => self.data.push(b),
+            }
+        }
+    }
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+pub(crate) async fn copy_in<DB, D, R, I>(
+    rows: I,
+    pool: &PgPool,
+    options: &CopyOptions,
+) -> Result<u64, crate::Error>
+where
+    DB: SqlxDatabase,
+    D: SqlxDatabase,
+    R: Resource<DB, D>,
+    I: IntoIterator<Item = (Option<R::ResourceID>, R)>,
+{
+    if DB::NAME != Postgres::NAME {
+        return Err(crate::Error::DialectMismatch {
+            expected: DB::NAME,
+            found: Postgres::NAME.to_string(),
+        });
+    }
+    let table = R::table_schema();
+    let target = match table.schema {
+        Some(schema) => format!("{}.{}", quote(schema), quote(table.table)),
+        None => quote(table.table),
+    };
+    let columns = table
+        .columns
+        .iter()
+        .map(|c| quote(c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if !options.upsert {
+        let mut conn = pool.acquire().await?;
+        return stream::<DB, D, R, I>(&mut conn, &target, &columns, &table, rows, options).await;
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(&format!(
+        "CREATE TEMP TABLE {STAGING} (LIKE {target} INCLUDING DEFAULTS) ON COMMIT DROP"
+    ))
+    .execute(&mut *tx)
+    .await?;
+    stream::<DB, D, R, I>(&mut tx, STAGING, &columns, &table, rows, options).await?;
+
+    // Later rows win over earlier ones with the same key, as if upserted one
+    // by one; Postgres won't update a row twice in one statement.
+    let keys = table
+        .primary_key
+        .iter()
+        .map(|k| quote(k))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let set = table
+        .columns
+        .iter()
+        .filter(|c| !table.primary_key.contains(&c.name))
+        .map(|c| format!("{0} = EXCLUDED.{0}", quote(c.name)))
+        .collect::<Vec<_>>();
+    let on_conflict = if set.is_empty() {
+        "ON CONFLICT DO NOTHING".to_string()
+    } else {
+        format!(
+            "ON CONFLICT ON CONSTRAINT {} DO UPDATE SET {}",
+            quote(table.constraint),
+            set.join(", ")
+        )
+    };
+    let merged = sqlx::query(&format!(
+        "INSERT INTO {target} ( {columns} ) \
+         SELECT DISTINCT ON ({keys}) {columns} FROM {STAGING} ORDER BY {keys}, ctid DESC \
+         {on_conflict}"
+    ))
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(merged.rows_affected())
+}
+
+async fn stream<DB, D, R, I>(
+    conn: &mut PgConnection,
+    target: &str,
+    columns: &str,
+    table: &TableSchema,
+    rows: I,
+    options: &CopyOptions,
+) -> Result<u64, crate::Error>
+where
+    DB: SqlxDatabase,
+    D: SqlxDatabase,
+    R: Resource<DB, D>,
+    I: IntoIterator<Item = (Option<R::ResourceID>, R)>,
+{
+    let format = match options.format {
+        CopyFormat::Text => "text",
+        CopyFormat::Binary => "binary",
+    };
+    let mut copy = conn
+        .copy_in_raw(&format!(
+            "COPY {target} ( {columns} ) FROM STDIN (FORMAT {format})"
+        ))
+        .await?;
+    // The server keeps waiting for data unless the copy is aborted.
+    match send::<DB, D, R, I>(&mut copy, table, rows, options).await {
+        Ok(()) => Ok(copy.finish().await?),
+        Err(e) => {
+            let _ = copy.abort(e.to_string()).await;
+            Err(e)
+        }
+    }
+}
+
+/// `id`, else the key `resource` carries in its own fields, else a generated
+/// one.
+pub(crate) async fn copy_key<DB, D, R>(
+    id: Option<R::ResourceID>,
+    resource: &R,
+) -> Result<R::ResourceID, crate::Error>
+where
+    DB: SqlxDatabase,
+    D: SqlxDatabase,
+    R: Resource<DB, D>,
+{
+    match id.or_else(|| resource.row_key()) {
+        Some(id) => Ok(id),
+        None => match <R as GenResourceID<D>>::gen_id().await {
+            Err(crate::Error::GenIdFailure) => Err(crate::Error::CopyKeyRequired {
+                table: R::table_schema().table,
+            }),
+            key => key,
+        },
+    }
+}
+
+async fn send<DB, D, R, I>(
+    copy: &mut PgCopyIn<&mut PgConnection>,
+    table: &TableSchema,
+    rows: I,
+    options: &CopyOptions,
+) -> Result<(), crate::Error>
+where
+    DB: SqlxDatabase,
+    D: SqlxDatabase,
+    R: Resource<DB, D>,
+    I: IntoIterator<Item = (Option<R::ResourceID>, R)>,
+{
+    let mut row = CopyRow::new(options.format);
+    for (id, resource) in rows {
+        let id = copy_key::<DB, D, R>(id, &resource).await?;
+        row.begin(table.columns.len());
+        resource.copy_row(&id, &mut row)?;
+        row.end();
+        if row.len() >= FLUSH_AT {
+            copy.send(row.take()).await?;
+        }
+    }
+    row.finish();
+    copy.send(row.take()).await?;
+    Ok(())
+}
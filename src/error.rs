@@ -1,13 +1,233 @@
-#[derive(Debug, PartialEq, thiserror::Error)]
+use sqlx::error::{DatabaseError, ErrorKind};
+
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("key not set")]
     GenIdFailure,
-    #[error("crypto error: `{0}`")]
-    DbExecuteFailure(String),
+    #[error("database error: `{0}`")]
+    DbExecuteFailure(#[source] sqlx::Error),
+    #[error("`{resource}` with key `{key}` not found")]
+    NotFound { resource: &'static str, key: String },
+    #[error("statements for `{expected}` can't run on `{found}`")]
+    DialectMismatch {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("unique violation: `{source}`")]
+    UniqueViolation {
+        constraint: Option<String>,
+        table: Option<String>,
+        #[source]
+        source: sqlx::Error,
+    },
+    #[error("foreign key violation: `{source}`")]
+    ForeignKeyViolation {
+        constraint: Option<String>,
+        table: Option<String>,
+        #[source]
+        source: sqlx::Error,
+    },
+    /// Postgres `serialization_failure` (40001).
+    #[error("serialization failure: `{source}`")]
+    SerializationFailure {
+        #[source]
+        source: sqlx::Error,
+    },
+    /// Postgres `deadlock_detected` (40P01), or SQLite `SQLITE_BUSY`/`SQLITE_LOCKED`.
+    #[error("deadlock: `{source}`")]
+    Deadlock {
+        #[source]
+        source: sqlx::Error,
+    },
+    #[error("connection lost: `{source}`")]
+    ConnectionLost {
+        #[source]
+        source: sqlx::Error,
+    },
+    /// A value couldn't be written as JSON, for `COPY` text or a
+    /// `#[resource(json)]` column.
+    #[error("encode error: `{0}`")]
+    Encode(#[source] serde_json::Error),
+    /// [`crate::Resource::copy_in`] got a row without a key, for a resource
+    /// whose keys come from the database or need an executor.
+    #[error("`COPY` into `{table}` needs a key for every row")]
+    CopyKeyRequired { table: &'static str },
+    #[error("decode error: `{source}`")]
+    Decode {
+        column: Option<String>,
+        #[source]
+        source: sqlx::Error,
+    },
+}
+
+impl Error {
+    pub fn not_found<K: serde::Serialize>(resource: &'static str, key: &K) -> Self {
+        let key = serde_json::to_string(key).unwrap_or_default();
+        Self::NotFound { resource, key }
+    }
+
+    /// Whether running the same statements again in a fresh transaction may
+    /// succeed, i.e. the failure came from a conflict with another transaction.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::SerializationFailure { .. } | Self::Deadlock { .. }
+        )
+    }
+
+    /// The sqlx error this one was classified from, if any.
+    pub fn as_sqlx(&self) -> Option<&sqlx::Error> {
+        match self {
+            Self::GenIdFailure
+            | Self::NotFound { .. }
+            | Self::DialectMismatch { .. }
+            | Self::CopyKeyRequired { .. }
+            | Self::Encode(_) => None,
+            Self::DbExecuteFailure(source)
+            | Self::UniqueViolation { source, .. }
+            | Self::ForeignKeyViolation { source, .. }
+            | Self::SerializationFailure { source }
+            | Self::Deadlock { source }
+            | Self::ConnectionLost { source }
+            | Self::Decode { source, .. } => Some(source),
+        }
+    }
+}
+
+// `sqlx::Error` isn't comparable, so sources are compared by their message.
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        use Error::*;
+
+        let same_source = || match (self.as_sqlx(), other.as_sqlx()) {
+            (Some(a), Some(b)) => a.to_string() == b.to_string(),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        match (self, other) {
+            (GenIdFailure, GenIdFailure) => true,
+            (
+                NotFound {
+                    resource: r1,
+                    key: k1,
+                },
+                NotFound {
+                    resource: r2,
+                    key: k2,
+                },
+            ) => r1 == r2 && k1 == k2,
+            (
+                DialectMismatch {
+                    expected: e1,
+                    found: f1,
+                },
+                DialectMismatch {
+                    expected: e2,
+                    found: f2,
+                },
+            ) => e1 == e2 && f1 == f2,
+            (
+                UniqueViolation {
+                    constraint: c1,
+                    table: t1,
+                    ..
+                },
+                UniqueViolation {
+                    constraint: c2,
+                    table: t2,
+                    ..
+                },
+            )
+            | (
+                ForeignKeyViolation {
+                    constraint: c1,
+                    table: t1,
+                    ..
+                },
+                ForeignKeyViolation {
+                    constraint: c2,
+                    table: t2,
+                    ..
+                },
+            ) => c1 == c2 && t1 == t2 && same_source(),
+            (Decode { column: c1, .. }, Decode { column: c2, .. }) => c1 == c2 && same_source(),
+            (CopyKeyRequired { table: t1 }, CopyKeyRequired { table: t2 }) => t1 == t2,
+            (Encode(e1), Encode(e2)) => e1.to_string() == e2.to_string(),
+            (DbExecuteFailure(_), DbExecuteFailure(_))
+            | (SerializationFailure { .. }, SerializationFailure { .. })
+            | (Deadlock { .. }, Deadlock { .. })
+            | (ConnectionLost { .. }, ConnectionLost { .. }) => same_source(),
+            _ => false,
+        }
+    }
+}
+
+const PG_SERIALIZATION_FAILURE: &str = "40001";
+const PG_DEADLOCK_DETECTED: &str = "40P01";
+const PG_CONNECTION_EXCEPTION_CLASS: &str = "08";
+
+// Primary result codes; the extended ones keep them in the low byte.
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+
+fn classify_database(db: &dyn DatabaseError) -> Option<fn(sqlx::Error) -> Error> {
+    let code = db.code()?;
+    if db.try_downcast_ref::<sqlx::sqlite::SqliteError>().is_some() {
+        return match code.parse::<i32>().ok()? & 0xff {
+            SQLITE_BUSY | SQLITE_LOCKED => Some(|source| Error::Deadlock { source }),
+            _ => None,
+        };
+    }
+    match &*code {
+        PG_SERIALIZATION_FAILURE => Some(|source| Error::SerializationFailure { source }),
+        PG_DEADLOCK_DETECTED => Some(|source| Error::Deadlock { source }),
+        code if code.starts_with(PG_CONNECTION_EXCEPTION_CLASS) => {
+            Some(|source| Error::ConnectionLost { source })
+        }
+        _ => None,
+    }
 }
 
 impl From<sqlx::Error> for Error {
     fn from(value: sqlx::Error) -> Self {
-        Self::DbExecuteFailure(value.to_string())
+        match &value {
+            sqlx::Error::Database(db) => {
+                let constraint = db.constraint().map(str::to_owned);
+                let table = db.table().map(str::to_owned);
+                match db.kind() {
+                    ErrorKind::UniqueViolation => Self::UniqueViolation {
+                        constraint,
+                        table,
+                        source: value,
+                    },
+                    ErrorKind::ForeignKeyViolation => Self::ForeignKeyViolation {
+                        constraint,
+                        table,
+                        source: value,
+                    },
+                    _ => match classify_database(db.as_ref()) {
+                        Some(variant) => variant(value),
+                        None => Self::DbExecuteFailure(value),
+                    },
+                }
+            }
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => Self::ConnectionLost { source: value },
+            sqlx::Error::ColumnDecode { index, .. } => Self::Decode {
+                column: Some(index.clone()),
+                source: value,
+            },
+            sqlx::Error::ColumnNotFound(column) => Self::Decode {
+                column: Some(column.clone()),
+                source: value,
+            },
+            sqlx::Error::Decode(_) | sqlx::Error::ColumnIndexOutOfBounds { .. } => Self::Decode {
+                column: None,
+                source: value,
+            },
+            _ => Self::DbExecuteFailure(value),
+        }
     }
 }
@@ -0,0 +1,156 @@
+use sqlx::{
+    database::Database as SqlxDatabase, Any, AnyConnection, Executor as SqlxExecutor, PgConnection,
+    Pool, Postgres, Sqlite, SqliteConnection,
+};
+
+use crate::{Action, ExecutionOutcome, IdempotencyStore};
+
+/// An executor that knows which database it talks to, so a `Resource<DB>` can
+/// refuse to run `DB`'s statements against a different backend.
+pub trait ResourceExecutor<'c>: SqlxExecutor<'c> {
+    /// The same connection or pool for a shorter lifetime, so more than one
+    /// statement can run on it.
+    type Reborrow<'r>: ResourceExecutor<'r, Database = Self::Database>
+    where
+        Self: 'r;
+
+    /// The backend's `Database::NAME`, e.g. `"PostgreSQL"` or `"SQLite"`.
+    fn backend_name(&self) -> String;
+
+    fn reborrow(&mut self) -> Self::Reborrow<'_>;
+
+    fn ensure_backend<DB: SqlxDatabase>(&self) -> Result<(), crate::Error> {
+        let found = self.backend_name();
+        if found == DB::NAME {
+            Ok(())
+        } else {
+            Err(crate::Error::DialectMismatch {
+                expected: DB::NAME,
+                found,
+            })
+        }
+    }
+}
+
+/// A driver [`Commands`](crate::Commands) run on: `Any`, or a native database.
+pub trait Driver: SqlxDatabase {
+    type Executor<'c>: ResourceExecutor<'c, Database = Self>;
+
+    fn executor(conn: &mut Self::Connection) -> Self::Executor<'_>;
+
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+
+    /// Runs `cmd` through `store`, whose statements bind and decode values
+    /// only a concrete driver can vouch for.
+    async fn execute_idempotent<A: Action<Self>>(
+        store: &IdempotencyStore,
+        cmd: &A,
+        conn: &mut Self::Connection,
+    ) -> Result<ExecutionOutcome, crate::Error>;
+}
+
+macro_rules! driver {
+    ($db:ty, $conn:ty) => {
+        impl Driver for $db {
+            type Executor<'c> = &'c mut $conn;
+
+            fn executor(conn: &mut $conn) -> &mut $conn {
+                conn
+            }
+
+            fn rows_affected(result: &Self::QueryResult) -> u64 {
+                result.rows_affected()
+            }
+
+            async fn execute_idempotent<A: Action<Self>>(
+                store: &IdempotencyStore,
+                cmd: &A,
+                conn: &mut $conn,
+            ) -> Result<ExecutionOutcome, crate::Error> {
+                store.execute::<Self, A>(cmd, conn).await
+            }
+        }
+    };
+}
+
+driver!(Any, AnyConnection);
+
+impl<'c> ResourceExecutor<'c> for &'c mut AnyConnection {
+    type Reborrow<'r>
+        = &'r mut AnyConnection
+    where
+        Self: 'r;
+
+    fn backend_name(&self) -> String {
+        AnyConnection::backend_name(self).to_string()
+    }
+
+    fn reborrow(&mut self) -> Self::Reborrow<'_> {
+        &mut **self
+    }
+}
+
+impl<'c, 'p> ResourceExecutor<'c> for &'p Pool<Any> {
+    type Reborrow<'r>
+        = &'p Pool<Any>
+    where
+        Self: 'r;
+
+    fn backend_name(&self) -> String {
+        let options = self.connect_options();
+        let scheme = options.database_url.scheme();
+        [
+            (Postgres::URL_SCHEMES, Postgres::NAME),
+            (Sqlite::URL_SCHEMES, Sqlite::NAME),
+            #[cfg(feature = "mysql")]
+            (sqlx::MySql::URL_SCHEMES, sqlx::MySql::NAME),
+        ]
+        .into_iter()
+        .find(|(schemes, _)| schemes.contains(&scheme))
+        .map_or_else(|| scheme.to_string(), |(_, name)| name.to_string())
+    }
+    fn reborrow(&mut self) -> Self::Reborrow<'_> {
+        *self
+    }
+}
+
+macro_rules! native_executor {
+    ($db:ty, $conn:ty) => {
+        driver!($db, $conn);
+
+        impl<'c> ResourceExecutor<'c> for &'c mut $conn {
+            type Reborrow<'r>
+                = &'r mut $conn
+            where
+                Self: 'r;
+
+            fn backend_name(&self) -> String {
+                <$db as SqlxDatabase>::NAME.to_string()
+            }
+
+            fn reborrow(&mut self) -> Self::Reborrow<'_> {
+                &mut **self
+            }
+        }
+
+        impl<'c, 'p> ResourceExecutor<'c> for &'p Pool<$db> {
+            type Reborrow<'r>
+                = &'p Pool<$db>
+            where
+                Self: 'r;
+
+            fn backend_name(&self) -> String {
+                <$db as SqlxDatabase>::NAME.to_string()
+            }
+
+            fn reborrow(&mut self) -> Self::Reborrow<'_> {
+                *self
+            }
+        }
+    };
+}
+
+native_executor!(Postgres, PgConnection);
+native_executor!(Sqlite, SqliteConnection);
+#[cfg(feature = "mysql")]
+native_executor!(sqlx::MySql, sqlx::MySqlConnection);
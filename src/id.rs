@@ -0,0 +1,223 @@
+//! Key generators behind `#[resource(id = "...")]`.
+
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sqlx::database::Database as SqlxDatabase;
+
+use crate::{Error, GenResourceID};
+
+/// 2020-01-01T00:00:00Z, in milliseconds since the Unix epoch.
+const EPOCH_MS: u64 = 1_577_836_800_000;
+/// Bits after the timestamp, shared by the datacenter, worker and sequence.
+const NODE_AND_SEQUENCE_BITS: u32 = 22;
+
+/// What [`Snowflake::next_id`] does when the clock is behind the last
+/// timestamp it handed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollback {
+    /// Return [`Error::GenIdFailure`].
+    Fail,
+    /// Wait for the clock to catch up, if it's no further behind than this;
+    /// fail otherwise. [`Snowflake::next_id`] sleeps the thread meanwhile,
+    /// [`Snowflake::next_id_async`] the task.
+    Wait(Duration),
+}
+
+/// Time-ordered `i64` keys: 41 bits of milliseconds since the epoch, then the
+/// datacenter, the worker and a per-millisecond sequence. Unique across
+/// generators with distinct datacenter and worker ids.
+///
+/// The builders are `const`, so a generator can be a `static`.
+#[derive(Debug)]
+pub struct Snowflake {
+    epoch_ms: u64,
+    datacenter: u64,
+    worker: u64,
+    datacenter_bits: u32,
+    worker_bits: u32,
+    rollback: Rollback,
+    clock: fn() -> u64,
+    /// `(millisecond, sequence)` of the last id handed out.
+    last: Mutex<(u64, u64)>,
+}
+
+impl Snowflake {
+    /// 5 datacenter bits, 5 worker bits and a 12 bit sequence, counting from
+    /// 2020, failing on clock rollback.
+    pub const fn new(datacenter: u64, worker: u64) -> Self {
+        Snowflake {
+            epoch_ms: EPOCH_MS,
+            datacenter,
+            worker,
+            datacenter_bits: 5,
+            worker_bits: 5,
+            rollback: Rollback::Fail,
+            clock: unix_ms,
+            last: Mutex::new((0, 0)),
+        }
+        .checked()
+    }
+
+    /// Milliseconds since the Unix epoch that timestamps count from.
+    pub const fn epoch(mut self, epoch_ms: u64) -> Self {
+        self.epoch_ms = epoch_ms;
+        self
+    }
+
+    /// The sequence gets the rest of the 22 bits after the timestamp.
+    pub const fn bits(mut self, datacenter_bits: u32, worker_bits: u32) -> Self {
+        self.datacenter_bits = datacenter_bits;
+        self.worker_bits = worker_bits;
+        self.checked()
+    }
+
+    pub const fn on_rollback(mut self, rollback: Rollback) -> Self {
+        self.rollback = rollback;
+        self
+    }
+
+    /// Milliseconds since the Unix epoch, in place of the system clock.
+    pub(crate) const fn clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    const fn checked(self) -> Self {
+        assert!(
+            self.datacenter_bits + self.worker_bits < NODE_AND_SEQUENCE_BITS,
+            "no bits left for the sequence"
+        );
+        assert!(
+            self.datacenter >> self.datacenter_bits == 0,
+            "datacenter id out of range"
+        );
+        assert!(
+            self.worker >> self.worker_bits == 0,
+            "worker id out of range"
+        );
+        self
+    }
+
+    const fn sequence_bits(&self) -> u32 {
+        NODE_AND_SEQUENCE_BITS - self.datacenter_bits - self.worker_bits
+    }
+
+    fn elapsed(&self) -> Result<u64, Error> {
+        (self.clock)()
+            .checked_sub(self.epoch_ms)
+            .ok_or(Error::GenIdFailure)
+    }
+
+    /// The next id, or how long to wait before asking again. The lock is
+    /// only held to read and update the last id, never while waiting.
+    fn try_next(&self) -> Result<Result<i64, Duration>, Error> {
+        let mut last = self.last.lock().map_err(|_| Error::GenIdFailure)?;
+        let (last_ms, last_seq) = *last;
+        let now = self.elapsed()?;
+        if now < last_ms {
+            let behind = Duration::from_millis(last_ms - now);
+            return match self.rollback {
+                Rollback::Wait(max) if behind <= max => Ok(Err(behind)),
+                _ => Err(Error::GenIdFailure),
+            };
+        }
+        let sequence_bits = self.sequence_bits();
+        *last = match now == last_ms {
+            true if last_seq + 1 < 1 << sequence_bits => (now, last_seq + 1),
+            // Out of sequence numbers until the next millisecond.
+            true => return Ok(Err(Duration::from_millis(1))),
+            false => (now, 0),
+        };
+        let (ms, seq) = *last;
+        if ms >> (63 - NODE_AND_SEQUENCE_BITS) != 0 {
+            return Err(Error::GenIdFailure);
+        }
+        let node = self.datacenter << self.worker_bits | self.worker;
+        Ok(Ok(
+            (ms << NODE_AND_SEQUENCE_BITS | node << sequence_bits | seq) as i64,
+        ))
+    }
+
+    /// Greater than every id this generator handed out before. Sleeps the
+    /// thread until the next millisecond once its sequence runs out; async
+    /// callers want [`Snowflake::next_id_async`].
+    pub fn next_id(&self) -> Result<i64, Error> {
+        loop {
+            match self.try_next()? {
+                Ok(id) => return Ok(id),
+                Err(wait) => std::thread::sleep(wait),
+            }
+        }
+    }
+
+    /// [`Snowflake::next_id`], waiting on the Tokio timer instead.
+    pub async fn next_id_async(&self) -> Result<i64, Error> {
+        loop {
+            match self.try_next()? {
+                Ok(id) => return Ok(id),
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+}
+
+/// Keys a resource with its [`Snowflake`], implementing [`GenResourceID`]
+/// for every driver. Required by `#[resource(id = "snowflake")]`.
+///
+/// ```ignore
+/// static IDS: Snowflake = Snowflake::new(1, 3);
+///
+/// impl SnowflakeID for Message<'_> {
+///     fn snowflake() -> &'static Snowflake {
+///         &IDS
+///     }
+/// }
+/// ```
+pub trait SnowflakeID {
+    fn snowflake() -> &'static Snowflake;
+}
+
+impl<T: SnowflakeID, D: SqlxDatabase> GenResourceID<D> for T {
+    type Target = i64;
+
+    async fn gen_id() -> Result<i64, Error> {
+        T::snowflake().next_id_async().await
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// A random UUID, hyphenated.
+pub fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    hyphenated(&bytes)
+}
+
+/// A UUID led by the Unix time in milliseconds, so later ones sort after
+/// earlier ones.
+pub fn uuid_v7() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[..6].copy_from_slice(&unix_ms().to_be_bytes()[2..]);
+    bytes[6] = bytes[6] & 0x0f | 0x70;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    hyphenated(&bytes)
+}
+
+fn hyphenated(bytes: &[u8; 16]) -> String {
+    let mut s = String::with_capacity(36);
+    for (i, b) in bytes.iter().enumerate() {
+        if matches!(i, 4 | 6 | 8 | 10) {
+            s.push('-');
+        }
+        s.push_str(&format!("{b:02x}"));
+    }
+    s
+}
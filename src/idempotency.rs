@@ -0,0 +1,147 @@
+use sqlx::{
+    database::HasArguments, Encode, Executor as SqlxExecutor, FromRow, IntoArguments, Type,
+};
+
+use crate::{Action, ActionKind, Driver, ExecutionOutcome, ResourceExecutor, Trace};
+
+/// `sqlx::MySql::NAME`, which needs the `mysql` feature.
+const MYSQL: &str = "MySQL";
+
+/// A table of already processed `(trace, tag)` pairs. Each command claims its
+/// pair in the same transaction it runs in, so a resent command is reported as
+/// [`ActionKind::Duplicate`] instead of being applied twice.
+#[derive(Debug, Clone)]
+pub struct IdempotencyStore {
+    table: String,
+}
+
+impl IdempotencyStore {
+    pub fn new(table: impl Into<String>) -> Self {
+        IdempotencyStore {
+            table: table.into(),
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// For `backend`, a `Database::NAME`.
+    pub fn create_table_sql(&self, backend: &str) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} ( trace BIGINT NOT NULL, tag VARCHAR(255) NOT NULL, \
+             outcome TEXT, PRIMARY KEY (trace, tag) )",
+            self.quoted_table(backend)
+        )
+    }
+
+    pub async fn create_table<'c, E>(&self, executor: E) -> Result<(), crate::Error>
+    where
+        E: ResourceExecutor<'c>,
+    {
+        let sql = self.create_table_sql(&executor.backend_name());
+        executor.execute(sql.as_str()).await?;
+        Ok(())
+    }
+
+    /// Quoted the way the derive quotes identifiers on `backend`, a
+    /// `schema.table` name part by part.
+    fn quoted_table(&self, backend: &str) -> String {
+        let quote = if backend == MYSQL { '`' } else { '"' };
+        let parts: Vec<String> = self
+            .table
+            .split('.')
+            .map(|part| {
+                let part = part.replace(quote, &format!("{quote}{quote}"));
+                format!("{quote}{part}{quote}")
+            })
+            .collect();
+        parts.join(".")
+    }
+
+    /// Claims `(trace, tag)`, affecting no rows if it was already claimed.
+    pub(crate) fn claim_sql(&self, backend: &str) -> String {
+        let table = self.quoted_table(backend);
+        match backend {
+            MYSQL => format!("INSERT IGNORE INTO {table} ( trace, tag ) VALUES ( ?, ? )"),
+            _ => format!(
+                "INSERT INTO {table} ( trace, tag ) VALUES ( $1, $2 ) ON CONFLICT DO NOTHING"
+            ),
+        }
+    }
+
+    pub(crate) fn select_sql(&self, backend: &str) -> String {
+        let table = self.quoted_table(backend);
+        match backend {
+            MYSQL => format!("SELECT outcome FROM {table} WHERE trace = ? AND tag = ?"),
+            _ => format!("SELECT outcome FROM {table} WHERE trace = $1 AND tag = $2"),
+        }
+    }
+
+    pub(crate) fn record_sql(&self, backend: &str) -> String {
+        let table = self.quoted_table(backend);
+        match backend {
+            MYSQL => format!("UPDATE {table} SET outcome = ? WHERE trace = ? AND tag = ?"),
+            _ => format!("UPDATE {table} SET outcome = $1 WHERE trace = $2 AND tag = $3"),
+        }
+    }
+
+    /// Generic over the driver, instantiated by each [`Driver`] impl.
+    pub(crate) async fn execute<D, A>(
+        &self,
+        cmd: &A,
+        conn: &mut D::Connection,
+    ) -> Result<ExecutionOutcome, crate::Error>
+    where
+        D: Driver,
+        A: Action<D>,
+        for<'c> &'c mut D::Connection: ResourceExecutor<'c, Database = D>,
+        for<'q> <D as HasArguments<'q>>::Arguments: IntoArguments<'q, D>,
+        for<'q> i64: Encode<'q, D> + Type<D>,
+        for<'q> &'q str: Encode<'q, D> + Type<D>,
+        for<'q> Option<String>: Encode<'q, D> + Type<D>,
+        for<'r> (Option<String>,): FromRow<'r, D::Row>,
+    {
+        let Some((trace, tag)) = cmd.trace() else {
+            return cmd.execute(conn).await;
+        };
+
+        let backend = conn.backend_name();
+        let claimed = sqlx::query(&self.claim_sql(&backend))
+            .bind(trace)
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+        if D::rows_affected(&claimed) == 0 {
+            let stored: Option<String> = sqlx::query_scalar(&self.select_sql(&backend))
+                .bind(trace)
+                .bind(tag)
+                .fetch_one(&mut *conn)
+                .await?;
+            let key = stored
+                .and_then(|s| serde_json::from_str::<ExecutionOutcome>(&s).ok())
+                .map(|outcome| outcome.key)
+                .unwrap_or_default();
+            return Ok(ExecutionOutcome {
+                kind: ActionKind::Duplicate,
+                rows_affected: 0,
+                key,
+            });
+        }
+
+        let outcome = cmd.execute(&mut *conn).await?;
+        sqlx::query(&self.record_sql(&backend))
+            .bind(serde_json::to_string(&outcome).ok())
+            .bind(trace)
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+        Ok(outcome)
+    }
+}
+
+impl Default for IdempotencyStore {
+    fn default() -> Self {
+        Self::new("resource_commands")
+    }
+}
@@ -46,85 +46,340 @@
 //     }
 // }
 //
-// #[derive(Deserialize, Serialize, Debug)]
+// #[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
 // enum TestResources<'a> {
 //     #[serde(borrow)]
-//     Message(Command<Postgres, GeneralAction<Postgres, message::Message<'a>>>),
+//     Message(Command<GeneralAction<Postgres, message::Message<'a>>>),
 //     #[serde(borrow)]
-//     Group(Command<Postgres, GeneralAction<Postgres, group::Group<'a>>>),
-//     GroupMember(Command<Postgres, GeneralAction<Postgres, group::GroupMember>>),
-// }
-//
-// impl Resources for TestResources<'_> {}
-//
-// impl Action for TestResources<'_> {
-//     async fn execute<'c, E>(&self, executor: E) -> Result<(), crate::Error>
-//     where
-//         E: SqlxExecutor<'c, Database = Any>,
-//     {
-//         match self {
-//             TestResources::Message(r) => r.execute(executor).await,
-//             TestResources::Group(r) => r.execute(executor).await,
-//             TestResources::GroupMember(r) => r.execute(executor).await,
-//         }
-//     }
+//     Group(Command<GeneralAction<Postgres, group::Group<'a>>>),
+//     GroupMember(Command<GeneralAction<Postgres, group::GroupMember>>),
 // }
 #![feature(async_closure, associated_type_bounds, let_chains)]
 #![allow(unused, async_fn_in_trait)]
+extern crate self as resource;
+
 pub use resource_macros;
+pub use serde;
+pub use sqlx;
 
 mod error;
 pub use error::Error;
 
+mod outcome;
+pub use outcome::{ActionKind, ExecutionOutcome};
+
+mod retry;
+pub use retry::RetryPolicy;
+
+mod idempotency;
+pub use idempotency::IdempotencyStore;
+
+mod executor;
+pub use executor::{Driver, ResourceExecutor};
+
+mod copy;
+pub use copy::{CopyFormat, CopyOptions, CopyRow};
+
+pub mod id;
+
+mod schema;
+pub use schema::{
+    verify_schema, ColumnSchema, SchemaIssue, SchemaReport, TableReport, TableSchema,
+};
+
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 
 use sqlx::{database::Database as SqlxDatabase, Any, Executor as SqlxExecutor, Postgres, Sqlite};
 
-pub trait Resources: Action {}
+pub trait Resources: Trace + Serialize {}
 
-pub trait Action: Serialize {
-    async fn execute<'c, E>(&self, executor: E) -> Result<(), crate::Error>
+/// Something that can be run against an executor for the `D` driver: `Any`
+/// by default, or a native database such as `Postgres`.
+pub trait Action<D: SqlxDatabase = Any>: Trace + Serialize {
+    async fn execute<'c, E>(&self, executor: E) -> Result<ExecutionOutcome, crate::Error>
     where
-        E: SqlxExecutor<'c, Database = Any>;
+        E: ResourceExecutor<'c, Database = D>;
+
+    /// How many of the leading `actions` [`Action::execute_batch`] can run
+    /// as one statement, at least 1. Nothing batches by default.
+    fn batch_len(actions: &[&Self]) -> usize {
+        actions.len().min(1)
+    }
+
+    /// Runs actions grouped by [`Action::batch_len`], with the outcomes
+    /// running them one by one would have had. Any other slice runs one
+    /// action at a time.
+    async fn execute_batch<'c, E>(
+        actions: &[&Self],
+        executor: E,
+    ) -> Result<Vec<ExecutionOutcome>, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>,
+    {
+        execute_each(actions, executor).await
+    }
+}
+
+/// Runs `actions` one at a time, in order.
+async fn execute_each<'c, D, A, E>(
+    actions: &[&A],
+    mut executor: E,
+) -> Result<Vec<ExecutionOutcome>, crate::Error>
+where
+    D: SqlxDatabase,
+    A: Action<D> + ?Sized,
+    E: ResourceExecutor<'c, Database = D>,
+{
+    let mut outcomes = Vec::with_capacity(actions.len());
+    for action in actions {
+        outcomes.push(action.execute(executor.reborrow()).await?);
+    }
+    Ok(outcomes)
 }
 
-pub trait GenResourceID {
+/// Split out of [`Action`] since it doesn't depend on the driver.
+pub trait Trace {
+    /// The `(trace, tag)` pair an [`IdempotencyStore`] deduplicates on.
+    fn trace(&self) -> Option<(i64, &str)> {
+        None
+    }
+}
+
+/// Keys for rows inserted without one, through the `D` driver.
+pub trait GenResourceID<D: SqlxDatabase = Any> {
     type Target;
 
-    async fn gen_id() -> Result<Self::Target, crate::Error>;
+    /// A key that needs neither the resource nor the database. Also what
+    /// [`Resource::copy_in`] uses, having no `D` executor to offer.
+    async fn gen_id() -> Result<Self::Target, crate::Error> {
+        Err(crate::Error::GenIdFailure)
+    }
+
+    /// The key for `self`, run on the executor the row is then written with,
+    /// so inside `Commands::Multi` it sees and joins the same transaction.
+    async fn gen_id_with<'c, E>(&self, executor: E) -> Result<Self::Target, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>,
+    {
+        Self::gen_id().await
+    }
 }
 
-pub trait Resource<DB: SqlxDatabase>: GenResourceID<Target = Self::ResourceID> + Serialize {
+/// A row's types in `DB`, the same whichever driver runs its statements.
+pub trait ResourceTypes<DB: SqlxDatabase> {
     type ResourceID: Serialize;
+    /// What the read API returns: the resource with every borrowed field owned.
+    type Owned;
+    /// The resource with every column optional, applied by [`GeneralAction::Patch`].
+    type Patch: Serialize;
+}
+
+/// A row whose statements are written for `DB`, executed through the `D`
+/// driver: `Any`, or `DB` itself for native pools and connections.
+pub trait Resource<DB: SqlxDatabase, D: SqlxDatabase = Any>:
+    ResourceTypes<DB> + GenResourceID<D, Target = <Self as ResourceTypes<DB>>::ResourceID> + Serialize
+{
+    /// `CREATE TABLE IF NOT EXISTS` for the resource's table in `DB`,
+    /// including the named primary-key constraint upserts rely on.
+    fn create_table_sql() -> &'static str;
+
+    fn drop_table_sql() -> &'static str;
+
+    /// What [`verify_schema`] checks the live table against.
+    fn table_schema() -> TableSchema;
+
+    async fn create_table<'c, E>(executor: E) -> Result<(), crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>,
+    {
+        executor.ensure_backend::<DB>()?;
+        executor.execute(Self::create_table_sql()).await?;
+        Ok(())
+    }
 
     async fn insert<'c, E>(
         &self,
         id: &Option<Self::ResourceID>,
         executor: E,
-    ) -> Result<(), crate::Error>
+    ) -> Result<ExecutionOutcome, crate::Error>
     where
-        E: SqlxExecutor<'c, Database = Any>;
+        E: ResourceExecutor<'c, Database = D>;
 
     async fn upsert<'c, E>(
         &self,
         id: &Option<Self::ResourceID>,
         executor: E,
-    ) -> Result<(), crate::Error>
+    ) -> Result<ExecutionOutcome, crate::Error>
     where
-        E: SqlxExecutor<'c, Database = Any>;
+        E: ResourceExecutor<'c, Database = D>;
 
-    async fn update<'c, E>(&self, id: &Self::ResourceID, executor: E) -> Result<(), crate::Error>
+    /// Rows one [`Resource::insert_batch`] or [`Resource::upsert_batch`]
+    /// statement takes, staying under `DB`'s bound parameter limit.
+    fn batch_rows(kind: ActionKind) -> usize;
+
+    /// Inserts every row with a single multi-row statement, generating the
+    /// missing keys in order.
+    async fn insert_batch<'c, E>(
+        rows: &[(&Self, &Option<Self::ResourceID>)],
+        executor: E,
+    ) -> Result<Vec<ExecutionOutcome>, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>;
+
+    async fn upsert_batch<'c, E>(
+        rows: &[(&Self, &Option<Self::ResourceID>)],
+        executor: E,
+    ) -> Result<Vec<ExecutionOutcome>, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>;
+
+    /// Writes the key, then the fields, in [`Resource::table_schema`] order.
+    fn copy_row(&self, id: &Self::ResourceID, row: &mut CopyRow) -> Result<(), crate::Error>;
+
+    /// The key of a resource declaring its keys on its fields, which
+    /// [`Resource::copy_in`] uses for rows without one.
+    fn row_key(&self) -> Option<Self::ResourceID> {
+        None
+    }
+
+    /// Streams `rows` into the table through `COPY ... FROM STDIN`, or with
+    /// [`CopyOptions::upsert`] into a staging table merged with
+    /// `INSERT ... ON CONFLICT`. Postgres only; returns the rows written.
+    /// A row without a key fails with [`crate::Error::CopyKeyRequired`] unless
+    /// [`GenResourceID::gen_id`] or the row's own fields provide one.
+    async fn copy_in<I>(
+        rows: I,
+        pool: &sqlx::PgPool,
+        options: &CopyOptions,
+    ) -> Result<u64, crate::Error>
     where
-        E: SqlxExecutor<'c, Database = Any>;
+        Self: Sized,
+        I: IntoIterator<Item = (Option<Self::ResourceID>, Self)>,
+    {
+        copy::copy_in::<DB, D, Self, I>(rows, pool, options).await
+    }
 
-    async fn drop<'c, E>(id: &Self::ResourceID, executor: E) -> Result<(), crate::Error>
+    async fn update<'c, E>(
+        &self,
+        id: &Self::ResourceID,
+        executor: E,
+    ) -> Result<ExecutionOutcome, crate::Error>
     where
-        E: SqlxExecutor<'c, Database = Any>;
+        E: ResourceExecutor<'c, Database = D>;
+
+    /// A missing row isn't an error; its outcome affected no rows.
+    async fn drop<'c, E>(
+        id: &Self::ResourceID,
+        executor: E,
+    ) -> Result<ExecutionOutcome, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>;
+
+    async fn patch<'c, E>(
+        id: &Self::ResourceID,
+        changes: &Self::Patch,
+        executor: E,
+    ) -> Result<ExecutionOutcome, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>;
+
+    async fn fetch_by_id<'c, E>(
+        id: &Self::ResourceID,
+        executor: E,
+    ) -> Result<Self::Owned, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>;
+
+    async fn fetch_optional_by_id<'c, E>(
+        id: &Self::ResourceID,
+        executor: E,
+    ) -> Result<Option<Self::Owned>, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>;
+
+    async fn exists<'c, E>(id: &Self::ResourceID, executor: E) -> Result<bool, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>;
+}
+
+/// `Any` in sqlx 0.7 reports NULL values as non-null with a `NULL` type name,
+/// so derived `FromRow` impls check both before decoding an `Option` column.
+#[doc(hidden)]
+pub fn __is_null<R>(row: &R, column: &'static str) -> Result<bool, sqlx::Error>
+where
+    R: sqlx::Row,
+    &'static str: sqlx::ColumnIndex<R>,
+{
+    use sqlx::{TypeInfo, ValueRef};
+
+    let value = row.try_get_raw(column)?;
+    Ok(value.is_null() || value.type_info().name() == "NULL")
+}
+
+/// The text a `#[resource(json)]` field is bound as.
+#[doc(hidden)]
+pub fn __to_json<T: Serialize + ?Sized>(value: &T) -> Result<String, crate::Error> {
+    serde_json::to_string(value).map_err(crate::Error::Encode)
+}
+
+/// Shows a byte key column of a generated key struct as lowercase hex.
+#[doc(hidden)]
+pub struct __Hex<'a>(pub &'a [u8]);
+
+impl std::fmt::Display for __Hex<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// Reads a `#[resource(json)]` column back from its text.
+#[doc(hidden)]
+pub fn __from_json<T: serde::de::DeserializeOwned>(
+    column: &'static str,
+    json: &str,
+) -> Result<T, sqlx::Error> {
+    serde_json::from_str(json).map_err(|e| sqlx::Error::ColumnDecode {
+        index: format!("{column:?}"),
+        source: Box::new(e),
+    })
+}
+
+/// `head ( row ), ( row ), ...` with `rows` copies of `row`, numbered
+/// placeholders (`${}`) counting up across rows.
+#[doc(hidden)]
+pub fn __values_sql(head: &str, row: &[&str], rows: usize, tail: &str) -> String {
+    let mut sql = head.to_string();
+    for r in 0..rows {
+        sql.push_str(if r == 0 { " ( " } else { ", ( " });
+        for (c, placeholder) in row.iter().enumerate() {
+            if c > 0 {
+                sql.push_str(", ");
+            }
+            sql.push_str(&placeholder.replace("{}", &(r * row.len() + c + 1).to_string()));
+        }
+        sql.push_str(" )");
+    }
+    if !tail.is_empty() {
+        sql.push(' ');
+        sql.push_str(tail);
+    }
+    sql
+}
+
+/// Patch fields tell an absent column from an explicit `null`, so a present
+/// value is always wrapped in `Some`, even when it is itself `None`.
+#[doc(hidden)]
+pub fn __patch_field<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
-pub enum GeneralAction<DB: SqlxDatabase, R: Resource<DB>> {
+pub enum GeneralAction<DB: SqlxDatabase, R: ResourceTypes<DB>> {
     Insert {
         id: Option<R::ResourceID>,
         resource: R,
@@ -138,50 +393,145 @@ pub enum GeneralAction<DB: SqlxDatabase, R: Resource<DB>> {
         resource: R,
     },
     Drop(R::ResourceID),
+    Patch {
+        id: R::ResourceID,
+        changes: R::Patch,
+    },
 }
 
-impl<DB: SqlxDatabase, R: Resource<DB>> Action for GeneralAction<DB, R> {
-    async fn execute<'c, E>(&self, executor: E) -> Result<(), crate::Error>
+impl<DB, D, R> Action<D> for GeneralAction<DB, R>
+where
+    DB: SqlxDatabase,
+    D: SqlxDatabase,
+    R: Resource<DB, D>,
+{
+    async fn execute<'c, E>(&self, executor: E) -> Result<ExecutionOutcome, crate::Error>
     where
-        E: SqlxExecutor<'c, Database = Any>,
+        E: ResourceExecutor<'c, Database = D>,
     {
         match self {
-            GeneralAction::Insert { id, resource } => resource.insert(id, executor).await,
-            GeneralAction::Upsert { id, resource } => resource.upsert(id, executor).await,
-            GeneralAction::Update { id, resource } => resource.update(id, executor).await,
-            GeneralAction::Drop(id) => R::drop(id, executor).await,
+            GeneralAction::Insert { id, resource } => {
+                Resource::<DB, D>::insert(resource, id, executor).await
+            }
+            GeneralAction::Upsert { id, resource } => {
+                Resource::<DB, D>::upsert(resource, id, executor).await
+            }
+            GeneralAction::Update { id, resource } => {
+                Resource::<DB, D>::update(resource, id, executor).await
+            }
+            GeneralAction::Drop(id) => <R as Resource<DB, D>>::drop(id, executor).await,
+            GeneralAction::Patch { id, changes } => {
+                <R as Resource<DB, D>>::patch(id, changes, executor).await
+            }
+        }
+    }
+
+    /// Consecutive inserts, or consecutive upserts, of the resource. An
+    /// upsert repeating a key in the batch starts the next one, since
+    /// Postgres won't touch a row twice in one statement.
+    fn batch_len(actions: &[&Self]) -> usize {
+        let kind = match actions.first() {
+            Some(GeneralAction::Insert { .. }) => ActionKind::Insert,
+            Some(GeneralAction::Upsert { .. }) => ActionKind::Upsert,
+            first => return usize::from(first.is_some()),
+        };
+        let mut keys = std::collections::HashSet::new();
+        let len = actions
+            .iter()
+            .take(<R as Resource<DB, D>>::batch_rows(kind))
+            .take_while(|action| match (kind, action) {
+                (ActionKind::Insert, GeneralAction::Insert { .. }) => true,
+                (ActionKind::Upsert, GeneralAction::Upsert { id: Some(id), .. }) => {
+                    keys.insert(serde_json::to_string(id).unwrap_or_default())
+                }
+                // Keys declared on fields come from the row itself.
+                (ActionKind::Upsert, GeneralAction::Upsert { id: None, resource }) => {
+                    match <R as Resource<DB, D>>::row_key(resource) {
+                        Some(id) => keys.insert(serde_json::to_string(&id).unwrap_or_default()),
+                        None => true,
+                    }
+                }
+                _ => false,
+            })
+            .count();
+        len.max(1)
+    }
+
+    async fn execute_batch<'c, E>(
+        actions: &[&Self],
+        executor: E,
+    ) -> Result<Vec<ExecutionOutcome>, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>,
+    {
+        let rows: Vec<_> = actions
+            .iter()
+            .filter_map(|action| match action {
+                GeneralAction::Insert { id, resource } | GeneralAction::Upsert { id, resource } => {
+                    Some((resource, id))
+                }
+                _ => None,
+            })
+            .collect();
+        match actions {
+            [GeneralAction::Insert { .. }, ..] if rows.len() == actions.len() => {
+                <R as Resource<DB, D>>::insert_batch(&rows, executor).await
+            }
+            [GeneralAction::Upsert { .. }, ..] if rows.len() == actions.len() => {
+                <R as Resource<DB, D>>::upsert_batch(&rows, executor).await
+            }
+            _ => execute_each(actions, executor).await,
         }
     }
 }
 
+impl<DB: SqlxDatabase, R: ResourceTypes<DB>> Trace for GeneralAction<DB, R> {}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
-pub struct Command<A>
-where
-    A: Action,
-{
+pub struct Command<A> {
     pub trace: i64,
     pub action: A,
     pub tag: String,
 }
 
-impl<A> Command<A>
-where
-    A: Action,
-{
+impl<A> Command<A> {
     pub fn new(trace: i64, action: A, tag: String) -> Command<A> {
         Command { trace, action, tag }
     }
 }
 
-impl<A> Action for Command<A>
+impl<D, A> Action<D> for Command<A>
 where
-    A: Action,
+    D: SqlxDatabase,
+    A: Action<D>,
 {
-    async fn execute<'c, E>(&self, executor: E) -> Result<(), crate::Error>
+    async fn execute<'c, E>(&self, executor: E) -> Result<ExecutionOutcome, crate::Error>
+    where
+        E: ResourceExecutor<'c, Database = D>,
+    {
+        Action::<D>::execute(&self.action, executor).await
+    }
+
+    fn batch_len(actions: &[&Self]) -> usize {
+        let actions: Vec<&A> = actions.iter().map(|cmd| &cmd.action).collect();
+        <A as Action<D>>::batch_len(&actions)
+    }
+
+    async fn execute_batch<'c, E>(
+        actions: &[&Self],
+        executor: E,
+    ) -> Result<Vec<ExecutionOutcome>, crate::Error>
     where
-        E: SqlxExecutor<'c, Database = Any>,
+        E: ResourceExecutor<'c, Database = D>,
     {
-        self.action.execute(executor).await
+        let actions: Vec<&A> = actions.iter().map(|cmd| &cmd.action).collect();
+        <A as Action<D>>::execute_batch(&actions, executor).await
+    }
+}
+
+impl<A> Trace for Command<A> {
+    fn trace(&self) -> Option<(i64, &str)> {
+        Some((self.trace, &self.tag))
     }
 }
 
@@ -192,43 +542,214 @@ pub enum Commands<RS> {
     Multi(Vec<RS>),
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct ExecuteOptions {
+    pub retry: RetryPolicy,
+    pub idempotency: Option<IdempotencyStore>,
+}
+
+impl ExecuteOptions {
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn idempotent(mut self, store: IdempotencyStore) -> Self {
+        self.idempotency = Some(store);
+        self
+    }
+}
+
 impl<RS> Commands<RS>
 where
     RS: Resources,
 {
     #[allow(dead_code)]
-    pub async fn execute<'c>(&self, pool: &'c sqlx::Pool<sqlx::Any>) -> Result<(), crate::Error> {
-        match self {
-            Commands::Single(cmd) => {
-                cmd.execute(pool).await?;
+    pub async fn execute<D>(
+        &self,
+        pool: &sqlx::Pool<D>,
+    ) -> Result<Commands<ExecutionOutcome>, crate::Error>
+    where
+        D: Driver,
+        RS: Action<D>,
+    {
+        self.execute_with(pool, &ExecuteOptions::default()).await
+    }
+
+    pub async fn execute_with<D>(
+        &self,
+        pool: &sqlx::Pool<D>,
+        options: &ExecuteOptions,
+    ) -> Result<Commands<ExecutionOutcome>, crate::Error>
+    where
+        D: Driver,
+        RS: Action<D>,
+    {
+        options.retry.run(|| self.execute_once(pool, options)).await
+    }
+
+    /// Runs every command under its own savepoint: a failing command is rolled
+    /// back on its own and the rest still commit. Returns one result per command.
+    pub async fn execute_partial<D>(
+        &self,
+        pool: &sqlx::Pool<D>,
+        options: &ExecuteOptions,
+    ) -> Result<Vec<Result<ExecutionOutcome, crate::Error>>, crate::Error>
+    where
+        D: Driver,
+        RS: Action<D>,
+    {
+        options
+            .retry
+            .run(|| self.execute_partial_once(pool, options))
+            .await
+    }
+
+    async fn execute_once<D>(
+        &self,
+        pool: &sqlx::Pool<D>,
+        options: &ExecuteOptions,
+    ) -> Result<Commands<ExecutionOutcome>, crate::Error>
+    where
+        D: Driver,
+        RS: Action<D>,
+    {
+        let outcome = match (self, &options.idempotency) {
+            (Commands::Single(cmd), None) => {
+                let mut conn = pool.acquire().await?;
+                Commands::Single(cmd.execute(D::executor(&mut conn)).await?)
+            }
+            (Commands::Single(cmd), Some(store)) => {
+                let mut tx = pool.begin().await?;
+                let outcome = D::execute_idempotent(store, cmd, tx.as_mut()).await?;
+                tx.commit().await?;
+                Commands::Single(outcome)
             }
-            Commands::Multi(cmds) => {
+            (Commands::Multi(cmds), Some(store)) => {
                 let mut tx = pool.begin().await?;
                 let exec = tx.as_mut();
+                let mut outcomes = Vec::with_capacity(cmds.len());
                 for cmd in cmds {
-                    cmd.execute(&mut *exec).await?;
+                    outcomes.push(D::execute_idempotent(store, cmd, &mut *exec).await?);
+                }
+                tx.commit().await?;
+                Commands::Multi(outcomes)
+            }
+            // Runs of inserts or upserts of one resource share a statement.
+            (Commands::Multi(cmds), None) => {
+                let mut tx = pool.begin().await?;
+                let exec = tx.as_mut();
+                let mut outcomes = Vec::with_capacity(cmds.len());
+                let cmds: Vec<&RS> = cmds.iter().collect();
+                let mut rest = cmds.as_slice();
+                while !rest.is_empty() {
+                    let (batch, tail) =
+                        rest.split_at(<RS as Action<D>>::batch_len(rest).clamp(1, rest.len()));
+                    match batch {
+                        [cmd] => outcomes.push(cmd.execute(D::executor(&mut *exec)).await?),
+                        _ => outcomes.extend(
+                            <RS as Action<D>>::execute_batch(batch, D::executor(&mut *exec))
+                                .await?,
+                        ),
+                    }
+                    rest = tail;
                 }
                 tx.commit().await?;
+                Commands::Multi(outcomes)
             }
         };
-        Ok(())
+        Ok(outcome)
+    }
+
+    async fn execute_partial_once<D>(
+        &self,
+        pool: &sqlx::Pool<D>,
+        options: &ExecuteOptions,
+    ) -> Result<Vec<Result<ExecutionOutcome, crate::Error>>, crate::Error>
+    where
+        D: Driver,
+        RS: Action<D>,
+    {
+        let cmds = match self {
+            Commands::Single(cmd) => std::slice::from_ref(cmd),
+            Commands::Multi(cmds) => cmds.as_slice(),
+        };
+
+        let mut tx = pool.begin().await?;
+        let mut report = Vec::with_capacity(cmds.len());
+        for cmd in cmds {
+            // Nested transactions are savepoints.
+            let mut savepoint = sqlx::Connection::begin(tx.as_mut()).await?;
+            let res = match &options.idempotency {
+                Some(store) => D::execute_idempotent(store, cmd, savepoint.as_mut()).await,
+                None => cmd.execute(D::executor(savepoint.as_mut())).await,
+            };
+            match res {
+                Ok(_) => savepoint.commit().await?,
+                Err(_) => savepoint.rollback().await?,
+            }
+            report.push(res);
+        }
+        tx.commit().await?;
+        Ok(report)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::{
-        Any, Command, Deserialize, GenResourceID, GeneralAction, Postgres, Resource, Serialize,
-        Sqlite, SqlxDatabase, SqlxExecutor,
+        Action, ActionKind, Any, Command, Commands, CopyFormat, CopyOptions, CopyRow, Deserialize,
+        ExecuteOptions, ExecutionOutcome, GenResourceID, GeneralAction, IdempotencyStore, Postgres,
+        Resource, ResourceExecutor, Resources, RetryPolicy, SchemaIssue, Serialize, Sqlite,
+        SqlxDatabase, SqlxExecutor,
     };
+    use std::time::Duration;
+
+    async fn sqlite_pool() -> sqlx::Pool<Any> {
+        sqlite_pool_at("sqlite::memory:").await
+    }
+
+    async fn sqlite_pool_at(url: &str) -> sqlx::Pool<Any> {
+        sqlx::any::install_default_drivers();
+        let pool = sqlx::any::AnyPoolOptions::new()
+            .max_connections(1)
+            .after_connect(|conn, _| {
+                Box::pin(async move {
+                    conn.execute("PRAGMA busy_timeout = 0").await?;
+                    Ok(())
+                })
+            })
+            .connect(url)
+            .await
+            .unwrap();
+        <Message as Resource<Sqlite>>::create_table(&pool)
+            .await
+            .unwrap();
+        pool
+    }
+
+    fn message(content: &str) -> Message<'_> {
+        Message {
+            typ: "typ",
+            addr_typ: "addr_typ",
+            addr: 0,
+            topic: "topic",
+            content,
+            sender: 1111,
+            receiver: None,
+            timestamp: 0,
+            message_type: "message_type",
+            stream: "stream",
+        }
+    }
 
-    #[derive(Deserialize, Serialize, Debug)]
+    #[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
     enum Server<'a> {
         #[serde(borrow)]
         Message(Command<GeneralAction<Postgres, Message<'a>>>),
     }
 
-    #[derive(Deserialize, Serialize, Debug)]
+    #[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
     enum Client<'a> {
         #[serde(borrow)]
         Message(Command<GeneralAction<Sqlite, Message<'a>>>),
@@ -263,11 +784,11 @@ mod test {
         pub timestamp: i64,
     }
 
-    impl GenResourceID for Message<'_> {
+    impl<D: SqlxDatabase> GenResourceID<D> for Message<'_> {
         type Target = i64;
 
         async fn gen_id() -> Result<i64, crate::Error> {
-            todo!()
+            Ok(42)
         }
     }
 
@@ -335,4 +856,1365 @@ mod test {
         let client: Client = serde_json::from_str(&server_str).unwrap();
         println!("client: {client:?}");
     }
+
+    #[test]
+    fn sqlite_fetch() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            let m = message("content");
+
+            <Message as Resource<Sqlite>>::insert(&m, &Some(1), &pool)
+                .await
+                .unwrap();
+
+            let owned = <Message as Resource<Sqlite>>::fetch_by_id(&1, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned.content, m.content);
+            assert_eq!(owned.sender, m.sender);
+            assert_eq!(owned.receiver, None);
+
+            assert!(<Message as Resource<Sqlite>>::exists(&1, &pool)
+                .await
+                .unwrap());
+            assert!(!<Message as Resource<Sqlite>>::exists(&2, &pool)
+                .await
+                .unwrap());
+            let missing = <Message as Resource<Sqlite>>::fetch_optional_by_id(&2, &pool)
+                .await
+                .unwrap();
+            assert_eq!(missing, None);
+        });
+    }
+
+    /// Table and column names that are reserved words or mixed case.
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        schema_name = "slep",
+        pg_table_name = "group",
+        sqlite_table_name = "group",
+        primary_key = "id:i64",
+        constraint = "slep_group_pkey"
+    )]
+    pub struct Group {
+        pub order: i64,
+        pub user: Option<String>,
+        #[resource(name = "createdBy")]
+        pub created_by: i64,
+    }
+
+    impl<D: SqlxDatabase> GenResourceID<D> for Group {
+        type Target = i64;
+
+        async fn gen_id() -> Result<i64, crate::Error> {
+            Ok(7)
+        }
+    }
+
+    #[test]
+    fn sqlite_reserved_identifiers() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            <Group as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let group = Group {
+                order: 1,
+                user: Some("user".to_string()),
+                created_by: 2,
+            };
+            <Group as Resource<Sqlite>>::insert(&group, &None, &pool)
+                .await
+                .unwrap();
+            <Group as Resource<Sqlite>>::upsert(&group, &Some(7), &pool)
+                .await
+                .unwrap();
+            let changes = GroupPatch {
+                order: Some(3),
+                ..Default::default()
+            };
+            <Group as Resource<Sqlite>>::patch(&7, &changes, &pool)
+                .await
+                .unwrap();
+
+            let owned = <Group as Resource<Sqlite>>::fetch_by_id(&7, &pool)
+                .await
+                .unwrap();
+            assert_eq!((owned.order, owned.created_by), (3, 2));
+            let (column,): (String,) =
+                sqlx::query_as("SELECT name FROM pragma_table_info('group') WHERE cid = 3")
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(column, "createdBy");
+
+            <Group as Resource<Sqlite>>::drop(&7, &pool).await.unwrap();
+            assert!(!<Group as Resource<Sqlite>>::exists(&7, &pool)
+                .await
+                .unwrap());
+        });
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        schema_name = "slep",
+        pg_table_name = "counter",
+        sqlite_table_name = "counter",
+        primary_key = "id:i64",
+        constraint = "slep_counter_pkey",
+        id = "autoincrement"
+    )]
+    pub struct Counter {
+        pub name: String,
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        schema_name = "slep",
+        pg_table_name = "token",
+        sqlite_table_name = "token",
+        primary_key = "id:String",
+        constraint = "slep_token_pkey",
+        id = "uuid_v7"
+    )]
+    pub struct Token {
+        pub user: i64,
+    }
+
+    static EVENT_IDS: crate::id::Snowflake = crate::id::Snowflake::new(2, 7);
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        schema_name = "slep",
+        pg_table_name = "event",
+        sqlite_table_name = "event",
+        primary_key = "id:i64",
+        constraint = "slep_event_pkey",
+        id = "snowflake"
+    )]
+    pub struct Event {
+        pub name: String,
+    }
+
+    impl crate::id::SnowflakeID for Event {
+        fn snowflake() -> &'static crate::id::Snowflake {
+            &EVENT_IDS
+        }
+    }
+
+    #[test]
+    fn sqlite_generated_keys() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            <Counter as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let counter = |name: &str| Counter {
+                name: name.to_string(),
+            };
+            let first = <Counter as Resource<Sqlite>>::insert(&counter("a"), &None, &pool)
+                .await
+                .unwrap();
+            let second = <Counter as Resource<Sqlite>>::upsert(&counter("b"), &None, &pool)
+                .await
+                .unwrap();
+            assert_eq!(
+                (first.key::<i64>().unwrap(), second.key::<i64>().unwrap()),
+                (1, 2)
+            );
+            <Counter as Resource<Sqlite>>::insert(&counter("c"), &Some(10), &pool)
+                .await
+                .unwrap();
+            // Batches hold one row each, inserted the same way.
+            let insert = GeneralAction::<Sqlite, _>::Insert {
+                id: None,
+                resource: counter("d"),
+            };
+            type Insert = GeneralAction<Sqlite, Counter>;
+            assert_eq!(<Insert as Action>::batch_len(&[&insert, &insert]), 1);
+            let outcomes = <Insert as Action>::execute_batch(&[&insert], &pool)
+                .await
+                .unwrap();
+            assert_eq!(outcomes[0].key::<i64>().unwrap(), 11);
+            let owned = <Counter as Resource<Sqlite>>::fetch_by_id(&11, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned.name, "d");
+            assert!(matches!(
+                <Counter as GenResourceID>::gen_id().await,
+                Err(crate::Error::GenIdFailure)
+            ));
+
+            <Token as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let outcome = <Token as Resource<Sqlite>>::insert(&Token { user: 1 }, &None, &pool)
+                .await
+                .unwrap();
+            let key: String = outcome.key().unwrap();
+            assert_eq!((key.len(), &key[14..15]), (36, "7"));
+            assert!(<Token as Resource<Sqlite>>::exists(&key, &pool)
+                .await
+                .unwrap());
+
+            <Event as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let event = Event {
+                name: "a".to_string(),
+            };
+            let outcome = <Event as Resource<Sqlite>>::insert(&event, &None, &pool)
+                .await
+                .unwrap();
+            // Datacenter and worker of `EVENT_IDS`.
+            let key: i64 = outcome.key().unwrap();
+            assert_eq!((key >> 17 & 0b11111, key >> 12 & 0b11111), (2, 7));
+            assert!(<Event as Resource<Sqlite>>::exists(&key, &pool)
+                .await
+                .unwrap());
+        });
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        schema_name = "slep",
+        pg_table_name = "membership",
+        sqlite_table_name = "membership",
+        constraint = "slep_membership_pkey"
+    )]
+    pub struct Membership {
+        #[resource(primary_key)]
+        pub user: std::string::String,
+        #[resource(primary_key, name = "groupId")]
+        pub group: i64,
+        pub level: i16,
+    }
+
+    #[test]
+    fn sqlite_field_keys() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            <Membership as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let member = Membership {
+                user: "ann".to_string(),
+                group: 1,
+                level: 2,
+            };
+            let outcome = <Membership as Resource<Sqlite>>::insert(&member, &None, &pool)
+                .await
+                .unwrap();
+            assert_eq!(
+                outcome.key,
+                serde_json::json!({ "user": "ann", "group": 1 })
+            );
+            let key: MembershipKey = outcome.key().unwrap();
+            assert_eq!(key.to_string(), "user=ann, group=1");
+            let bob = MembershipKey {
+                user: "bob".to_string(),
+                group: 1,
+            };
+            <Membership as Resource<Sqlite>>::upsert(&member, &Some(bob.clone()), &pool)
+                .await
+                .unwrap();
+
+            let owned = <Membership as Resource<Sqlite>>::fetch_by_id(&bob, &pool)
+                .await
+                .unwrap();
+            assert_eq!(
+                owned,
+                Membership {
+                    user: "bob".to_string(),
+                    ..member
+                }
+            );
+            let (column,): (String,) =
+                sqlx::query_as("SELECT name FROM pragma_table_info('membership') WHERE pk = 2")
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(column, "groupId");
+        });
+    }
+
+    #[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
+    enum Memberships {
+        Membership(Command<GeneralAction<Sqlite, Membership>>),
+    }
+
+    #[test]
+    fn sqlite_field_key_upserts() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            <Membership as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let upsert = |level| GeneralAction::<Sqlite, _>::Upsert {
+                id: None,
+                resource: Membership {
+                    user: "ann".to_string(),
+                    group: 1,
+                    level,
+                },
+            };
+            // The same key twice, which Postgres won't upsert in one statement.
+            type Upsert = GeneralAction<Sqlite, Membership>;
+            assert_eq!(<Upsert as Action>::batch_len(&[&upsert(1), &upsert(2)]), 1);
+
+            let cmds = [1, 2].map(|level| Memberships::membership(0, upsert(level), "Join"));
+            let Commands::Multi(outcomes) =
+                Commands::Multi(cmds.into()).execute(&pool).await.unwrap()
+            else {
+                unreachable!()
+            };
+            assert!(outcomes.iter().all(|o| o.rows_affected == 1));
+            let key = MembershipKey {
+                user: "ann".to_string(),
+                group: 1,
+            };
+            let owned = <Membership as Resource<Sqlite>>::fetch_by_id(&key, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned.level, 2);
+        });
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        pg_table_name = "badge",
+        sqlite_table_name = "badge",
+        constraint = "badge_pkey",
+        drivers = "sqlite"
+    )]
+    pub struct Badge {
+        #[resource(primary_key)]
+        pub name: std::string::String,
+        pub points: i64,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
+    enum Badges {
+        Badge(Command<GeneralAction<Sqlite, Badge>>),
+    }
+
+    #[test]
+    fn sqlite_native_commands() {
+        tokio_test::block_on(async {
+            let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            <Badge as Resource<Sqlite, Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let store = IdempotencyStore::default();
+            store.create_table(&pool).await.unwrap();
+            let insert = |name: &str, points| {
+                let resource = Badge {
+                    name: name.to_string(),
+                    points,
+                };
+                GeneralAction::Insert { id: None, resource }
+            };
+
+            let cmds = vec![
+                Badges::badge(1, insert("gold", 3), "Award"),
+                Badges::badge(2, insert("silver", 2), "Award"),
+            ];
+            let Commands::Multi(outcomes) = Commands::Multi(cmds).execute(&pool).await.unwrap()
+            else {
+                unreachable!()
+            };
+            assert_eq!(outcomes.len(), 2);
+
+            let options = ExecuteOptions::default().idempotent(store);
+            let cmd = || Commands::Single(Badges::badge(3, insert("bronze", 1), "Award"));
+            let first = cmd().execute_with(&pool, &options).await.unwrap();
+            let Commands::Single(again) = cmd().execute_with(&pool, &options).await.unwrap() else {
+                unreachable!()
+            };
+            assert!(matches!(first, Commands::Single(ref o) if o.kind == ActionKind::Insert));
+            assert_eq!(again.kind, ActionKind::Duplicate);
+
+            let total: i64 = sqlx::query_scalar("SELECT SUM(points) FROM badge")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(total, 6);
+        });
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+    pub struct Layout {
+        pub theme: String,
+        pub columns: Vec<u8>,
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        schema_name = "slep",
+        pg_table_name = "profile",
+        sqlite_table_name = "profile",
+        primary_key = "id:i64",
+        constraint = "slep_profile_pkey"
+    )]
+    pub struct Profile {
+        #[resource(json)]
+        pub tags: Vec<String>,
+        #[resource(json)]
+        pub layout: Option<Layout>,
+    }
+
+    impl<D: SqlxDatabase> GenResourceID<D> for Profile {
+        type Target = i64;
+    }
+
+    #[test]
+    fn sqlite_json_columns() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            <Profile as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let profile = Profile {
+                tags: vec!["a".to_string(), "b\"c".to_string()],
+                layout: Some(Layout {
+                    theme: "dark".to_string(),
+                    columns: vec![1, 2],
+                }),
+            };
+            <Profile as Resource<Sqlite>>::insert(&profile, &Some(1), &pool)
+                .await
+                .unwrap();
+            let owned = <Profile as Resource<Sqlite>>::fetch_by_id(&1, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned, profile);
+            let (tags,): (String,) = sqlx::query_as("SELECT tags FROM profile")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(tags, r#"["a","b\"c"]"#);
+
+            let changes = ProfilePatch {
+                layout: Some(None),
+                ..Default::default()
+            };
+            <Profile as Resource<Sqlite>>::patch(&1, &changes, &pool)
+                .await
+                .unwrap();
+            let owned = <Profile as Resource<Sqlite>>::fetch_by_id(&1, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned.layout, None);
+            let (null,): (i64,) = sqlx::query_as("SELECT layout IS NULL FROM profile")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(null, 1);
+
+            sqlx::query("UPDATE profile SET tags = '{}'")
+                .execute(&pool)
+                .await
+                .unwrap();
+            let err = <Profile as Resource<Sqlite>>::fetch_by_id(&1, &pool)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::Decode { .. }), "{err:?}");
+        });
+    }
+
+    /// Keyed by queue, numbered from a counter row in the same database.
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        schema_name = "slep",
+        pg_table_name = "ticket",
+        sqlite_table_name = "ticket",
+        primary_key = "id:i64",
+        constraint = "slep_ticket_pkey"
+    )]
+    pub struct Ticket {
+        pub queue: i64,
+    }
+
+    impl<D: SqlxDatabase> GenResourceID<D> for Ticket
+    where
+        i64: sqlx::Type<D> + for<'r> sqlx::Decode<'r, D>,
+        usize: sqlx::ColumnIndex<D::Row>,
+    {
+        type Target = i64;
+
+        async fn gen_id_with<'c, E>(&self, executor: E) -> Result<i64, crate::Error>
+        where
+            E: ResourceExecutor<'c, Database = D>,
+        {
+            use sqlx::Row;
+
+            let row = executor
+                .fetch_one("UPDATE ticket_seq SET n = n + 1 RETURNING n")
+                .await?;
+            Ok(self.queue * 1000 + row.try_get::<i64, _>(0)?)
+        }
+    }
+
+    #[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
+    enum Tickets {
+        Ticket(Command<GeneralAction<Sqlite, Ticket>>),
+    }
+
+    #[test]
+    fn sqlite_gen_id_in_transaction() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            <Ticket as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            pool.execute(
+                "CREATE TABLE ticket_seq (n INTEGER NOT NULL); INSERT INTO ticket_seq VALUES (0)",
+            )
+            .await
+            .unwrap();
+            let insert = |id, queue| {
+                let action = GeneralAction::Insert {
+                    id,
+                    resource: Ticket { queue },
+                };
+                Tickets::ticket(0, action, "Open")
+            };
+
+            let cmds = Commands::Multi(vec![insert(None, 1), insert(None, 1), insert(None, 2)]);
+            let Commands::Multi(outcomes) = cmds.execute(&pool).await.unwrap() else {
+                unreachable!()
+            };
+            let keys: Vec<i64> = outcomes.iter().map(|o| o.key().unwrap()).collect();
+            assert_eq!(keys, [1001, 1002, 2003]);
+
+            // The counter rolls back with the rows it numbered.
+            let err = Commands::Multi(vec![insert(None, 3), insert(Some(1001), 1)])
+                .execute(&pool)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::UniqueViolation { .. }));
+            let outcome = <Ticket as Resource<Sqlite>>::insert(&Ticket { queue: 3 }, &None, &pool)
+                .await
+                .unwrap();
+            assert_eq!(outcome.key::<i64>().unwrap(), 3004);
+        });
+    }
+
+    /// Numbered after the highest key already in its table.
+    #[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+    #[resource(
+        schema_name = "slep",
+        pg_table_name = "invoice",
+        sqlite_table_name = "invoice",
+        primary_key = "id:i64",
+        constraint = "slep_invoice_pkey"
+    )]
+    pub struct Invoice {
+        pub total: i64,
+    }
+
+    impl<D: SqlxDatabase> GenResourceID<D> for Invoice
+    where
+        i64: sqlx::Type<D> + for<'r> sqlx::Decode<'r, D>,
+        usize: sqlx::ColumnIndex<D::Row>,
+    {
+        type Target = i64;
+
+        async fn gen_id_with<'c, E>(&self, executor: E) -> Result<i64, crate::Error>
+        where
+            E: ResourceExecutor<'c, Database = D>,
+        {
+            use sqlx::Row;
+
+            let row = executor
+                .fetch_one("SELECT COALESCE(MAX(id), 0) + 1 FROM invoice")
+                .await?;
+            Ok(row.try_get::<i64, _>(0)?)
+        }
+    }
+
+    #[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
+    enum Invoices {
+        Invoice(Command<GeneralAction<Sqlite, Invoice>>),
+        Counter(Command<GeneralAction<Sqlite, Counter>>),
+    }
+
+    #[test]
+    fn sqlite_batch_keys_from_table() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            <Invoice as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let invoices = [1, 2, 3, 4].map(|total| Invoice { total });
+            let rows = [
+                (&invoices[0], &None),
+                (&invoices[1], &Some(10)),
+                (&invoices[2], &Some(20)),
+                (&invoices[3], &None),
+            ];
+            let outcomes = <Invoice as Resource<Sqlite>>::insert_batch(&rows, &pool)
+                .await
+                .unwrap();
+            let keys: Vec<i64> = outcomes.iter().map(|o| o.key().unwrap()).collect();
+            assert_eq!(keys, [1, 10, 20, 21]);
+
+            // Grouped into one batch, but numbered as if run one by one.
+            let insert = |total| {
+                let action = GeneralAction::Insert {
+                    id: None,
+                    resource: Invoice { total },
+                };
+                Invoices::invoice(0, action, "Bill")
+            };
+            let cmds = Commands::Multi(vec![insert(5), insert(6)]);
+            let Commands::Multi(outcomes) = cmds.execute(&pool).await.unwrap() else {
+                unreachable!()
+            };
+            let keys: Vec<i64> = outcomes.iter().map(|o| o.key().unwrap()).collect();
+            assert_eq!(keys, [22, 23]);
+        });
+    }
+
+    #[test]
+    fn sqlite_execute_batch_mixed() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            <Invoice as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            type Invoicing = GeneralAction<Sqlite, Invoice>;
+            let insert = Invoicing::Insert {
+                id: None,
+                resource: Invoice { total: 1 },
+            };
+            let update = Invoicing::Update {
+                id: 1,
+                resource: Invoice { total: 2 },
+            };
+            let outcomes =
+                <Invoicing as Action>::execute_batch(&[&insert, &update, &insert], &pool)
+                    .await
+                    .unwrap();
+            let kinds: Vec<_> = outcomes
+                .iter()
+                .map(|o| (o.kind, o.key().unwrap()))
+                .collect();
+            assert_eq!(
+                kinds,
+                [
+                    (ActionKind::Insert, 1),
+                    (ActionKind::Update, 1),
+                    (ActionKind::Insert, 2)
+                ]
+            );
+            let owned = <Invoice as Resource<Sqlite>>::fetch_by_id(&1, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned.total, 2);
+
+            // Runs of each variant go to that variant's payload.
+            <Counter as Resource<Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let invoice = |total| {
+                let action = Invoicing::Insert {
+                    id: None,
+                    resource: Invoice { total },
+                };
+                Invoices::invoice(0, action, "Bill")
+            };
+            let counter = Invoices::counter(
+                0,
+                GeneralAction::Insert {
+                    id: None,
+                    resource: Counter {
+                        name: "a".to_string(),
+                    },
+                },
+                "Count",
+            );
+            let (first, second) = (invoice(3), invoice(4));
+            let outcomes = <Invoices as Action>::execute_batch(&[&first, &counter, &second], &pool)
+                .await
+                .unwrap();
+            let keys: Vec<i64> = outcomes.iter().map(|o| o.key().unwrap()).collect();
+            assert_eq!(keys, [3, 1, 4]);
+        });
+    }
+
+    #[test]
+    fn sqlite_dialect_mismatch() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            let m = message("content");
+
+            let mismatch = crate::Error::DialectMismatch {
+                expected: "PostgreSQL",
+                found: "SQLite".to_string(),
+            };
+            let insert = GeneralAction::<Postgres, _>::Insert {
+                id: Some(1),
+                resource: m,
+            };
+            let server = Commands::Single(Server::message(0, insert, "Send"));
+            assert_eq!(server.execute(&pool).await.unwrap_err(), mismatch);
+
+            let mut conn = pool.acquire().await.unwrap();
+            let exists = <Message as Resource<Postgres>>::exists(&1, conn.as_mut()).await;
+            assert_eq!(exists, Err(mismatch));
+            assert!(!<Message as Resource<Sqlite>>::exists(&1, conn.as_mut())
+                .await
+                .unwrap());
+        });
+    }
+
+    #[test]
+    fn sqlite_native_pool() {
+        tokio_test::block_on(async {
+            let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+            <Message as Resource<Sqlite, Sqlite>>::create_table(&pool)
+                .await
+                .unwrap();
+            let m = Message {
+                receiver: Some(2222),
+                ..message("content")
+            };
+
+            let insert = Client::message(
+                0,
+                GeneralAction::Insert {
+                    id: None,
+                    resource: m,
+                },
+                "Send",
+            );
+            let outcome = Action::<Sqlite>::execute(&insert, &pool).await.unwrap();
+            assert_eq!(outcome.key::<i64>().unwrap(), 42);
+            let owned = <Message as Resource<Sqlite, Sqlite>>::fetch_by_id(&42, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned.receiver, Some(2222));
+        });
+    }
+
+    #[test]
+    fn create_table_sql() {
+        assert_eq!(
+            <Message as Resource<Postgres>>::create_table_sql(),
+            r#"CREATE TABLE IF NOT EXISTS "slep"."message" ( "id" BIGINT NOT NULL, "#.to_string()
+                + r#""typ" slep.message_type NOT NULL, "#
+                + r#""addr_typ" slep.message_addr_type NOT NULL, "addr" BIGINT NOT NULL, "#
+                + r#""stream" TEXT NOT NULL, "topic" TEXT NOT NULL, "#
+                + r#""message_type" TEXT NOT NULL, "content" TEXT NOT NULL, "#
+                + r#""sender" BIGINT NOT NULL, "receiver" BIGINT, "timestamp" BIGINT NOT NULL, "#
+                + r#"CONSTRAINT "slep_message_pkey" PRIMARY KEY ("id") )"#
+        );
+        assert_eq!(
+            <Message as Resource<Sqlite>>::drop_table_sql(),
+            r#"DROP TABLE IF EXISTS "message""#
+        );
+    }
+
+    #[test]
+    fn pg_verify_schema_sql() {
+        // Without a `schema_name`, `Any` binds `$1` as an integer NULL.
+        for sql in [crate::schema::PG_COLUMNS, crate::schema::PG_PRIMARY_KEY] {
+            assert!(
+                sql.contains("COALESCE($1::text, current_schema())"),
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn sqlite_verify_schema() {
+        tokio_test::block_on(async {
+            let table = <Message as Resource<Sqlite>>::table_schema();
+            let pool = sqlite_pool().await;
+            assert!(crate::verify_schema(&pool, std::slice::from_ref(&table))
+                .await
+                .unwrap()
+                .is_ok());
+            let err =
+                crate::verify_schema(&pool, &[<Message as Resource<Postgres>>::table_schema()])
+                    .await
+                    .unwrap_err();
+            assert!(matches!(err, crate::Error::DialectMismatch { .. }));
+
+            let pool = sqlx::any::AnyPoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            let report = crate::verify_schema(&pool, std::slice::from_ref(&table))
+                .await
+                .unwrap();
+            assert_eq!(report.tables[0].issues, vec![SchemaIssue::MissingTable]);
+
+            sqlx::query(
+                "CREATE TABLE message ( id INTEGER PRIMARY KEY, typ TEXT NOT NULL, \
+                 addr_typ TEXT NOT NULL, addr INTEGER NOT NULL, stream TEXT NOT NULL, \
+                 topic TEXT NOT NULL, message_type TEXT NOT NULL, content TEXT, \
+                 sender INTEGER NOT NULL, receiver INTEGER, sent_at INTEGER NOT NULL )",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            let report = crate::verify_schema(&pool, &[table]).await.unwrap();
+            assert!(!report.is_ok());
+            assert_eq!(
+                report.tables[0].issues,
+                vec![
+                    SchemaIssue::Nullability {
+                        column: "content".to_string(),
+                        expected: false,
+                        found: true,
+                    },
+                    SchemaIssue::MissingColumn {
+                        column: "timestamp".to_string(),
+                    },
+                    SchemaIssue::UnexpectedColumn {
+                        column: "sent_at".to_string(),
+                        nullable: false,
+                    },
+                ]
+            );
+        });
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn mysql_create_table_sql() {
+        assert_eq!(
+            <Message as Resource<sqlx::MySql>>::create_table_sql(),
+            "CREATE TABLE IF NOT EXISTS `message` ( `id` BIGINT NOT NULL, `typ` TEXT NOT NULL, \
+             `addr_typ` TEXT NOT NULL, `addr` BIGINT NOT NULL, `stream` TEXT NOT NULL, \
+             `topic` TEXT NOT NULL, `message_type` TEXT NOT NULL, `content` TEXT NOT NULL, \
+             `sender` BIGINT NOT NULL, `receiver` BIGINT, `timestamp` BIGINT NOT NULL, \
+             CONSTRAINT `slep_message_pkey` PRIMARY KEY (`id`) )"
+        );
+        let table = <Message as Resource<sqlx::MySql>>::table_schema();
+        assert_eq!((table.backend, table.table), ("MySQL", "message"));
+    }
+
+    #[test]
+    fn sqlite_update_not_found() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            let m = message("content");
+
+            let not_found = Err(crate::Error::NotFound {
+                resource: "Message",
+                key: "1".to_string(),
+            });
+            let update = GeneralAction::<Sqlite, _>::Update { id: 1, resource: m };
+            assert_eq!(update.execute(&pool).await, not_found);
+            let drop = GeneralAction::<Sqlite, Message>::Drop(1);
+            let outcome = drop.execute(&pool).await.unwrap();
+            assert_eq!((outcome.kind, outcome.rows_affected), (ActionKind::Drop, 0));
+
+            let GeneralAction::Update { resource, .. } = update else {
+                unreachable!()
+            };
+            <Message as Resource<Sqlite>>::insert(&resource, &Some(1), &pool)
+                .await
+                .unwrap();
+            let update = GeneralAction::<Sqlite, _>::Update {
+                id: 1,
+                resource: Message {
+                    content: "edited",
+                    ..resource
+                },
+            };
+            update.execute(&pool).await.unwrap();
+            let owned = <Message as Resource<Sqlite>>::fetch_by_id(&1, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned.content, "edited");
+            assert_eq!(drop.execute(&pool).await.unwrap().rows_affected, 1);
+        });
+    }
+
+    #[test]
+    fn sqlite_patch() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            let m = Message {
+                receiver: Some(2222),
+                ..message("content")
+            };
+            <Message as Resource<Sqlite>>::insert(&m, &Some(1), &pool)
+                .await
+                .unwrap();
+
+            let patch = GeneralAction::<Sqlite, Message>::Patch {
+                id: 1,
+                changes: MessagePatch {
+                    content: Some("patched"),
+                    receiver: Some(None),
+                    ..Default::default()
+                },
+            };
+            let patch_str = serde_json::to_string(&patch).unwrap();
+            assert_eq!(
+                patch_str,
+                r#"{"Patch":{"id":1,"changes":{"content":"patched","receiver":null}}}"#
+            );
+            let patch: GeneralAction<Sqlite, Message> = serde_json::from_str(&patch_str).unwrap();
+            patch.execute(&pool).await.unwrap();
+
+            let owned = <Message as Resource<Sqlite>>::fetch_by_id(&1, &pool)
+                .await
+                .unwrap();
+            assert_eq!(owned.content, "patched");
+            assert_eq!(owned.receiver, None);
+            assert_eq!(owned.topic, "topic");
+        });
+    }
+
+    #[test]
+    fn sqlite_commands_outcome() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            let m = message("content");
+            let insert = GeneralAction::Insert {
+                id: None,
+                resource: m,
+            };
+            let drop = GeneralAction::Drop(42);
+            let cmds = Commands::Multi(vec![
+                Client::message(0, insert, "Send"),
+                Client::message(1, drop, "Recall"),
+            ]);
+
+            let Commands::Multi(outcomes) = cmds.execute(&pool).await.unwrap() else {
+                unreachable!()
+            };
+            assert_eq!(outcomes.len(), 2);
+            assert_eq!(outcomes[0].kind, ActionKind::Insert);
+            assert_eq!(outcomes[0].rows_affected, 1);
+            assert_eq!(outcomes[0].key::<i64>().unwrap(), 42);
+            assert_eq!(outcomes[1].kind, ActionKind::Drop);
+            assert_eq!(outcomes[1].key::<i64>().unwrap(), 42);
+        });
+    }
+
+    #[test]
+    fn sqlite_batched_multi() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            // More inserts than one statement's parameters allow.
+            let mut cmds: Vec<Client> = (0..200)
+                .map(|id| {
+                    let insert = GeneralAction::Insert {
+                        id: Some(id),
+                        resource: message("content"),
+                    };
+                    Client::message(id, insert, "Send")
+                })
+                .collect();
+            let update = GeneralAction::Update {
+                id: 0,
+                resource: message("edited"),
+            };
+            cmds.push(Client::message(0, update, "Edit"));
+            for (id, content) in [(1, "up"), (200, "new"), (1, "up again")] {
+                let upsert = GeneralAction::Upsert {
+                    id: Some(id),
+                    resource: message(content),
+                };
+                cmds.push(Client::message(id, upsert, "Sync"));
+            }
+
+            let Commands::Multi(outcomes) = Commands::Multi(cmds).execute(&pool).await.unwrap()
+            else {
+                unreachable!()
+            };
+            assert_eq!(outcomes.len(), 204);
+            for (id, outcome) in outcomes[..200].iter().enumerate() {
+                assert_eq!(outcome.kind, ActionKind::Insert);
+                assert_eq!(outcome.rows_affected, 1);
+                assert_eq!(outcome.key::<i64>().unwrap(), id as i64);
+            }
+            assert_eq!(outcomes[200].kind, ActionKind::Update);
+            let upserts: Vec<_> = outcomes[201..]
+                .iter()
+                .map(|o| (o.kind, o.rows_affected, o.key::<i64>().unwrap()))
+                .collect();
+            assert_eq!(
+                upserts,
+                [
+                    (ActionKind::Upsert, 1, 1),
+                    (ActionKind::Upsert, 1, 200),
+                    (ActionKind::Upsert, 1, 1)
+                ]
+            );
+
+            let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM message")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(count, 201);
+            for (id, content) in [(0, "edited"), (1, "up again"), (199, "content")] {
+                let owned = <Message as Resource<Sqlite>>::fetch_by_id(&id, &pool)
+                    .await
+                    .unwrap();
+                assert_eq!(owned.content, content);
+            }
+
+            // A failing batch rolls everything back, as one by one would.
+            let insert = |id| {
+                let action = GeneralAction::Insert {
+                    id: Some(id),
+                    resource: message("content"),
+                };
+                Client::message(id, action, "Send")
+            };
+            let err = Commands::Multi(vec![insert(500), insert(1)])
+                .execute(&pool)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::UniqueViolation { .. }));
+            assert!(!<Message as Resource<Sqlite>>::exists(&500, &pool)
+                .await
+                .unwrap());
+        });
+    }
+
+    #[test]
+    fn copy_rows() {
+        let m = message("tab\there\\");
+        let mut row = CopyRow::new(CopyFormat::Text);
+        row.begin(11);
+        <Message as Resource<Postgres>>::copy_row(&m, &1, &mut row).unwrap();
+        row.end();
+        assert_eq!(
+            String::from_utf8(row.take()).unwrap(),
+            "1\ttyp\taddr_typ\t0\tstream\ttopic\tmessage_type\ttab\\there\\\\\t1111\t\\N\t0\n"
+        );
+
+        let mut row = CopyRow::new(CopyFormat::Binary);
+        row.begin(11);
+        <Message as Resource<Postgres>>::copy_row(&m, &1, &mut row).unwrap();
+        row.finish();
+        let data = row.take();
+        assert!(data.starts_with(b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0"));
+        // Field count, then the key's length and value.
+        assert_eq!(data[19..21], 11i16.to_be_bytes());
+        assert_eq!(data[21..25], 8i32.to_be_bytes());
+        assert_eq!(data[25..33], 1i64.to_be_bytes());
+        // `receiver` is NULL, followed by `timestamp` and the trailer.
+        assert_eq!(
+            data[data.len() - 18..data.len() - 14],
+            (-1i32).to_be_bytes()
+        );
+        assert_eq!(data[data.len() - 2..], (-1i16).to_be_bytes());
+    }
+
+    #[test]
+    fn copy_row_field_keys() {
+        let m = Membership {
+            user: "ann".to_string(),
+            group: 7,
+            level: 2,
+        };
+        let key = tokio_test::block_on(crate::copy::copy_key::<Postgres, Any, _>(None, &m));
+        let mut row = CopyRow::new(CopyFormat::Text);
+        row.begin(3);
+        <Membership as Resource<Postgres>>::copy_row(&m, &key.unwrap(), &mut row).unwrap();
+        row.end();
+        assert_eq!(String::from_utf8(row.take()).unwrap(), "ann\t7\t2\n");
+    }
+
+    #[test]
+    fn copy_row_non_finite() {
+        let mut row = CopyRow::new(CopyFormat::Text);
+        row.begin(6);
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1.5] {
+            row.field(&value).unwrap();
+        }
+        row.field(&Some(f32::INFINITY)).unwrap();
+        row.field(&None::<f32>).unwrap();
+        row.end();
+        assert_eq!(
+            String::from_utf8(row.take()).unwrap(),
+            "NaN\tInfinity\t-Infinity\t1.5\tInfinity\t\\N\n"
+        );
+    }
+
+    #[test]
+    fn copy_key_required() {
+        let counter = Counter {
+            name: "a".to_string(),
+        };
+        let key = crate::copy::copy_key::<Postgres, Any, _>(None, &counter);
+        assert_eq!(
+            tokio_test::block_on(key),
+            Err(crate::Error::CopyKeyRequired { table: "counter" })
+        );
+        let key = crate::copy::copy_key::<Postgres, Any, _>(Some(3), &counter);
+        assert_eq!(tokio_test::block_on(key), Ok(3));
+    }
+
+    #[test]
+    fn copy_in_dialect_mismatch() {
+        tokio_test::block_on(async {
+            let pool = sqlx::postgres::PgPoolOptions::new()
+                .connect_lazy("postgres://localhost/resource")
+                .unwrap();
+            let err = <Message as Resource<Sqlite>>::copy_in([], &pool, &CopyOptions::default())
+                .await
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::DialectMismatch { .. }));
+        });
+    }
+
+    #[test]
+    fn sqlite_unique_violation() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            let m = message("content");
+            <Message as Resource<Sqlite>>::insert(&m, &Some(1), &pool)
+                .await
+                .unwrap();
+
+            let err = <Message as Resource<Sqlite>>::insert(&m, &Some(1), &pool)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::UniqueViolation { .. }));
+            assert!(!err.is_retryable());
+            assert!(err.as_sqlx().is_some());
+        });
+    }
+
+    #[test]
+    fn sqlite_retry_on_busy() {
+        tokio_test::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let url = format!(
+                "sqlite://{}?mode=rwc",
+                dir.path().join("retry.db").display()
+            );
+            let pool = sqlite_pool_at(&url).await;
+
+            let mut holder = <sqlx::AnyConnection as sqlx::Connection>::connect(&url)
+                .await
+                .unwrap();
+            holder.execute("BEGIN IMMEDIATE").await.unwrap();
+
+            let m = message("content");
+            let insert = GeneralAction::Insert {
+                id: Some(1),
+                resource: m,
+            };
+            let cmds = Commands::Multi(vec![Client::Message(Command::new(
+                0,
+                insert,
+                "Send".to_string(),
+            ))]);
+
+            let err = cmds.execute(&pool).await.unwrap_err();
+            assert!(matches!(err, crate::Error::Deadlock { .. }));
+
+            let release = async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                holder.execute("ROLLBACK").await.unwrap();
+            };
+            let retry =
+                RetryPolicy::new(20).backoff(Duration::from_millis(10), Duration::from_millis(50));
+            let options = ExecuteOptions::default().retry(retry);
+            let (res, ()) = tokio::join!(cmds.execute_with(&pool, &options), release);
+            let Commands::Multi(outcomes) = res.unwrap() else {
+                unreachable!()
+            };
+            assert_eq!(outcomes[0].rows_affected, 1);
+        });
+    }
+
+    #[test]
+    fn idempotency_sql() {
+        let store = IdempotencyStore::new("slep.commands");
+        assert_eq!(
+            store.claim_sql("PostgreSQL"),
+            r#"INSERT INTO "slep"."commands" ( trace, tag ) VALUES ( $1, $2 ) ON CONFLICT DO NOTHING"#
+        );
+        assert_eq!(
+            store.claim_sql("MySQL"),
+            "INSERT IGNORE INTO `slep`.`commands` ( trace, tag ) VALUES ( ?, ? )"
+        );
+        assert_eq!(
+            store.select_sql("MySQL"),
+            "SELECT outcome FROM `slep`.`commands` WHERE trace = ? AND tag = ?"
+        );
+        assert_eq!(
+            store.record_sql("SQLite"),
+            r#"UPDATE "slep"."commands" SET outcome = $1 WHERE trace = $2 AND tag = $3"#
+        );
+        assert!(store
+            .create_table_sql("MySQL")
+            .starts_with("CREATE TABLE IF NOT EXISTS `slep`.`commands` ("));
+    }
+
+    #[test]
+    fn sqlite_idempotent_commands() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            let store = IdempotencyStore::default();
+            store.create_table(&pool).await.unwrap();
+            let options = ExecuteOptions::default().idempotent(store);
+
+            let m = message("content");
+            let insert = GeneralAction::Insert {
+                id: None,
+                resource: m,
+            };
+            let cmd = Client::Message(Command::new(7, insert, "Send".to_string()));
+            let single = Commands::Single(cmd);
+
+            let Commands::Single(first) = single.execute_with(&pool, &options).await.unwrap()
+            else {
+                unreachable!()
+            };
+            assert_eq!(first.kind, ActionKind::Insert);
+            let Commands::Single(replay) = single.execute_with(&pool, &options).await.unwrap()
+            else {
+                unreachable!()
+            };
+            assert_eq!(replay.kind, ActionKind::Duplicate);
+            assert_eq!(replay.rows_affected, 0);
+            assert_eq!(replay.key::<i64>().unwrap(), 42);
+
+            let Commands::Single(cmd) = single else {
+                unreachable!()
+            };
+            let drop = GeneralAction::Drop(42);
+            let multi = Commands::Multi(vec![
+                cmd,
+                Client::Message(Command::new(8, drop, "Recall".to_string())),
+            ]);
+            let Commands::Multi(outcomes) = multi.execute_with(&pool, &options).await.unwrap()
+            else {
+                unreachable!()
+            };
+            assert_eq!(outcomes[0].kind, ActionKind::Duplicate);
+            assert_eq!(outcomes[1].kind, ActionKind::Drop);
+        });
+    }
+
+    #[test]
+    fn sqlite_partial_commands() {
+        tokio_test::block_on(async {
+            let pool = sqlite_pool().await;
+            let m = || message("content");
+            let insert = |id| {
+                let action = GeneralAction::Insert {
+                    id: Some(id),
+                    resource: m(),
+                };
+                Client::Message(Command::new(id, action, "Send".to_string()))
+            };
+            let cmds = Commands::Multi(vec![insert(1), insert(1), insert(2)]);
+
+            let report = cmds
+                .execute_partial(&pool, &ExecuteOptions::default())
+                .await
+                .unwrap();
+            assert_eq!(report.len(), 3);
+            assert_eq!(report[0].as_ref().unwrap().kind, ActionKind::Insert);
+            assert!(matches!(
+                report[1],
+                Err(crate::Error::UniqueViolation { .. })
+            ));
+            assert_eq!(report[2].as_ref().unwrap().kind, ActionKind::Insert);
+
+            for id in [1, 2] {
+                assert!(<Message as Resource<Sqlite>>::exists(&id, &pool)
+                    .await
+                    .unwrap());
+            }
+        });
+    }
+
+    #[test]
+    fn snowflake_monotonic() {
+        use crate::id::Snowflake;
+
+        let ids = Snowflake::new(5, 1).bits(3, 7);
+        let mut last = ids.next_id().unwrap();
+        // Several milliseconds' worth, so the sequence runs out.
+        for _ in 0..10_000 {
+            let id = ids.next_id().unwrap();
+            assert!(id > last);
+            assert_eq!((id >> 19 & 0b111, id >> 12 & 0b111_1111), (5, 1));
+            last = id;
+        }
+    }
+
+    #[test]
+    fn snowflake_unique_across_threads() {
+        use crate::id::{Snowflake, SnowflakeID};
+        use std::collections::HashSet;
+
+        static IDS: Snowflake = Snowflake::new(1, 3);
+
+        struct Keyed;
+
+        impl SnowflakeID for Keyed {
+            fn snowflake() -> &'static Snowflake {
+                &IDS
+            }
+        }
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    (0..5_000)
+                        .map(|_| tokio_test::block_on(<Keyed as GenResourceID>::gen_id()).unwrap())
+                        .collect::<Vec<i64>>()
+                })
+            })
+            .collect();
+        let mut ids = HashSet::new();
+        for thread in threads {
+            let keys = thread.join().unwrap();
+            assert!(keys.windows(2).all(|w| w[0] < w[1]));
+            ids.extend(keys);
+        }
+        assert_eq!(ids.len(), 40_000);
+    }
+
+    #[test]
+    fn snowflake_clock_rollback() {
+        use crate::id::{Rollback, Snowflake};
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        // Milliseconds since the epoch below, ticking on every read.
+        static NOW: AtomicU64 = AtomicU64::new(1_000);
+        fn clock() -> u64 {
+            NOW.fetch_add(1, Ordering::SeqCst)
+        }
+
+        let failing = Snowflake::new(0, 0).epoch(0).clock(clock);
+        let waiting = Snowflake::new(0, 1)
+            .epoch(0)
+            .clock(clock)
+            .on_rollback(Rollback::Wait(Duration::from_millis(50)));
+        let first = failing.next_id().unwrap();
+        let before = waiting.next_id().unwrap();
+        NOW.fetch_sub(20, Ordering::SeqCst);
+        assert!(matches!(failing.next_id(), Err(crate::Error::GenIdFailure)));
+        let after = waiting.next_id().unwrap();
+        assert!(after > before);
+        NOW.fetch_sub(20, Ordering::SeqCst);
+        assert!(tokio_test::block_on(waiting.next_id_async()).unwrap() > after);
+        assert!(failing.next_id().unwrap() > first);
+
+        NOW.fetch_sub(100, Ordering::SeqCst);
+        assert!(matches!(waiting.next_id(), Err(crate::Error::GenIdFailure)));
+    }
 }
@@ -0,0 +1,36 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ActionKind {
+    Insert,
+    Upsert,
+    Update,
+    Drop,
+    Patch,
+    /// The command was already processed and has been skipped.
+    Duplicate,
+}
+
+/// What a single command did: how many rows it touched and which key it
+/// touched them under, including keys produced by `GenResourceID::gen_id`.
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct ExecutionOutcome {
+    pub kind: ActionKind,
+    pub rows_affected: u64,
+    pub key: serde_json::Value,
+}
+
+impl ExecutionOutcome {
+    pub fn new<K: Serialize>(kind: ActionKind, rows_affected: u64, key: &K) -> Self {
+        let key = serde_json::to_value(key).unwrap_or_default();
+        ExecutionOutcome {
+            kind,
+            rows_affected,
+            key,
+        }
+    }
+
+    pub fn key<K: DeserializeOwned>(&self) -> Result<K, serde_json::Error> {
+        K::deserialize(&self.key)
+    }
+}
@@ -0,0 +1,74 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::Error;
+
+/// How `Commands::execute_with` re-runs a batch that failed with a transient
+/// error. Every attempt runs in a fresh transaction.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retryable: fn(&Error) -> bool,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+            retryable: Error::is_retryable,
+        }
+    }
+
+    pub fn never() -> Self {
+        Self::new(1)
+    }
+
+    pub fn backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn retry_if(mut self, retryable: fn(&Error) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    pub(crate) async fn run<T, F, Fut>(&self, mut attempt_fn: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match attempt_fn().await {
+                Err(err) if attempt < self.max_attempts && (self.retryable)(&err) => {
+                    tokio::time::sleep(self.delay(attempt)).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Exponential backoff with full jitter: a random delay up to
+    /// `base_delay * 2^(attempt - 1)`, capped at `max_delay`.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let cap = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        rand::thread_rng().gen_range(Duration::ZERO..=cap)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::never()
+    }
+}
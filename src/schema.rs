@@ -0,0 +1,252 @@
+use sqlx::{database::Database as SqlxDatabase, Any, Pool, Postgres};
+
+use crate::ResourceExecutor;
+
+/// The table a `Resource<DB>` reads and writes, as the derive sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSchema {
+    /// `Database::NAME` of the backend the statements are written for.
+    pub backend: &'static str,
+    pub schema: Option<&'static str>,
+    pub table: &'static str,
+    /// Key columns first, then the fields, in binding order.
+    pub columns: &'static [ColumnSchema],
+    pub primary_key: &'static [&'static str],
+    pub constraint: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSchema {
+    pub name: &'static str,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaIssue {
+    MissingTable,
+    MissingColumn {
+        column: String,
+    },
+    /// A column the resource doesn't know about. Only a problem for writes
+    /// when it is `NOT NULL` without a default.
+    UnexpectedColumn {
+        column: String,
+        nullable: bool,
+    },
+    Nullability {
+        column: String,
+        expected: bool,
+        found: bool,
+    },
+    PrimaryKey {
+        expected: Vec<String>,
+        found: Vec<String>,
+    },
+    /// Postgres only: SQLite doesn't report constraint names and MySQL
+    /// names every primary key `PRIMARY`.
+    Constraint {
+        expected: String,
+        found: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableReport {
+    pub schema: Option<&'static str>,
+    pub table: &'static str,
+    pub issues: Vec<SchemaIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SchemaReport {
+    pub tables: Vec<TableReport>,
+}
+
+impl SchemaReport {
+    pub fn is_ok(&self) -> bool {
+        self.tables.iter().all(|t| t.issues.is_empty())
+    }
+}
+
+/// What the database has for a table, `columns` being `(name, nullable)`.
+struct LiveTable {
+    columns: Vec<(String, bool)>,
+    primary_key: Vec<String>,
+    constraint: Option<String>,
+}
+
+/// Compares each table against the live database (`information_schema` on
+/// Postgres and MySQL, `PRAGMA table_info` on SQLite), e.g. at startup:
+///
+/// ```ignore
+/// let report = verify_schema(&pool, &[<Message as Resource<Postgres>>::table_schema()]).await?;
+/// assert!(report.is_ok(), "{report:#?}");
+/// ```
+pub async fn verify_schema(
+    pool: &Pool<Any>,
+    tables: &[TableSchema],
+) -> Result<SchemaReport, crate::Error> {
+    let backend = ResourceExecutor::backend_name(&pool);
+    let mut report = SchemaReport::default();
+    for table in tables {
+        if table.backend != backend {
+            return Err(crate::Error::DialectMismatch {
+                expected: table.backend,
+                found: backend,
+            });
+        }
+        let live = match backend.as_str() {
+            Postgres::NAME => pg_table(pool, table).await?,
+            #[cfg(feature = "mysql")]
+            sqlx::MySql::NAME => mysql_table(pool, table).await?,
+            _ => sqlite_table(pool, table).await?,
+        };
+        report.tables.push(TableReport {
+            schema: table.schema,
+            table: table.table,
+            issues: compare(table, live),
+        });
+    }
+    Ok(report)
+}
+
+// `Any` binds a `None` schema as an integer NULL, which `COALESCE` can't
+// match with `name` unless it's cast.
+pub(crate) const PG_COLUMNS: &str = "SELECT column_name::text, is_nullable::text \
+     FROM information_schema.columns \
+     WHERE table_schema = COALESCE($1::text, current_schema()) AND table_name = $2 \
+     ORDER BY ordinal_position";
+
+pub(crate) const PG_PRIMARY_KEY: &str = "SELECT tc.constraint_name::text, kcu.column_name::text \
+     FROM information_schema.table_constraints tc \
+     JOIN information_schema.key_column_usage kcu \
+     USING (constraint_schema, constraint_name, table_schema, table_name) \
+     WHERE tc.constraint_type = 'PRIMARY KEY' \
+     AND tc.table_schema = COALESCE($1::text, current_schema()) AND tc.table_name = $2 \
+     ORDER BY kcu.ordinal_position";
+
+async fn pg_table(pool: &Pool<Any>, table: &TableSchema) -> Result<LiveTable, crate::Error> {
+    let columns: Vec<(String, String)> = sqlx::query_as(PG_COLUMNS)
+        .bind(table.schema)
+        .bind(table.table)
+        .fetch_all(pool)
+        .await?;
+    let keys: Vec<(String, String)> = sqlx::query_as(PG_PRIMARY_KEY)
+        .bind(table.schema)
+        .bind(table.table)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(LiveTable {
+        columns: columns
+            .into_iter()
+            .map(|(name, nullable)| (name, nullable == "YES"))
+            .collect(),
+        constraint: keys.first().map(|(constraint, _)| constraint.clone()),
+        primary_key: keys.into_iter().map(|(_, column)| column).collect(),
+    })
+}
+
+#[cfg(feature = "mysql")]
+async fn mysql_table(pool: &Pool<Any>, table: &TableSchema) -> Result<LiveTable, crate::Error> {
+    let columns: Vec<(String, String)> = sqlx::query_as(
+        "SELECT column_name, is_nullable FROM information_schema.columns \
+         WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ? \
+         ORDER BY ordinal_position",
+    )
+    .bind(table.schema)
+    .bind(table.table)
+    .fetch_all(pool)
+    .await?;
+    let keys: Vec<(String,)> = sqlx::query_as(
+        "SELECT column_name FROM information_schema.key_column_usage \
+         WHERE constraint_name = 'PRIMARY' \
+         AND table_schema = COALESCE(?, DATABASE()) AND table_name = ? \
+         ORDER BY ordinal_position",
+    )
+    .bind(table.schema)
+    .bind(table.table)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(LiveTable {
+        columns: columns
+            .into_iter()
+            .map(|(name, nullable)| (name, nullable == "YES"))
+            .collect(),
+        primary_key: keys.into_iter().map(|(column,)| column).collect(),
+        // Always `PRIMARY` on MySQL, whatever the table was declared with.
+        constraint: None,
+    })
+}
+
+async fn sqlite_table(pool: &Pool<Any>, table: &TableSchema) -> Result<LiveTable, crate::Error> {
+    let columns: Vec<(String, i64, i64)> =
+        sqlx::query_as("SELECT name, \"notnull\", pk FROM pragma_table_info($1) ORDER BY cid")
+            .bind(table.table)
+            .fetch_all(pool)
+            .await?;
+
+    let mut keys: Vec<(i64, String)> = columns
+        .iter()
+        .filter(|(_, _, pk)| *pk > 0)
+        .map(|(name, _, pk)| (*pk, name.clone()))
+        .collect();
+    keys.sort();
+    Ok(LiveTable {
+        columns: columns
+            .into_iter()
+            .map(|(name, notnull, _)| (name, notnull == 0))
+            .collect(),
+        primary_key: keys.into_iter().map(|(_, column)| column).collect(),
+        constraint: None,
+    })
+}
+
+fn compare(table: &TableSchema, live: LiveTable) -> Vec<SchemaIssue> {
+    if live.columns.is_empty() {
+        return vec![SchemaIssue::MissingTable];
+    }
+
+    let mut issues = Vec::new();
+    for column in table.columns {
+        match live.columns.iter().find(|(name, _)| name == column.name) {
+            None => issues.push(SchemaIssue::MissingColumn {
+                column: column.name.to_string(),
+            }),
+            // Key columns can't be NULL whatever they are declared as; SQLite
+            // still reports `INTEGER PRIMARY KEY` as nullable.
+            Some(_) if table.primary_key.contains(&column.name) => {}
+            Some((_, nullable)) if *nullable != column.nullable => {
+                issues.push(SchemaIssue::Nullability {
+                    column: column.name.to_string(),
+                    expected: column.nullable,
+                    found: *nullable,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, nullable) in &live.columns {
+        if !table.columns.iter().any(|c| c.name == name) {
+            issues.push(SchemaIssue::UnexpectedColumn {
+                column: name.clone(),
+                nullable: *nullable,
+            });
+        }
+    }
+
+    if live.primary_key != table.primary_key {
+        issues.push(SchemaIssue::PrimaryKey {
+            expected: table.primary_key.iter().map(|c| c.to_string()).collect(),
+            found: live.primary_key,
+        });
+    }
+    if table.backend == Postgres::NAME && live.constraint.as_deref() != Some(table.constraint) {
+        issues.push(SchemaIssue::Constraint {
+            expected: table.constraint.to_string(),
+            found: live.constraint,
+        });
+    }
+    issues
+}
@@ -0,0 +1,226 @@
+use resource::{resource_macros, GenResourceID, Resource};
+use serde::{Deserialize, Serialize};
+
+mod reexport {
+    pub use resource as res;
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+#[resource(
+    schema_name = "slep",
+    pg_table_name = "group",
+    sqlite_table_name = "group",
+    primary_key = "id:i64",
+    constraint = "slep_group_pkey"
+)]
+pub struct Group<'g> {
+    pub pid: Option<i64>,
+    #[serde(borrow)]
+    pub name: &'g str,
+    #[serde(borrow)]
+    pub des: &'g str,
+    pub timestamp: i64,
+}
+
+impl<D: resource::sqlx::Database> GenResourceID<D> for Group<'_> {
+    type Target = i64;
+
+    async fn gen_id() -> Result<i64, resource::Error> {
+        Err(resource::Error::GenIdFailure)
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+#[resource(
+    crate = "crate::reexport::res",
+    schema_name = "slep",
+    pg_table_name = "group_member",
+    sqlite_table_name = "group_member",
+    primary_key = "id:i64, gid:i64",
+    tuple_key,
+    constraint = "slep_group_member_pkey"
+)]
+pub struct GroupMember {
+    level: i16,
+    timestamp: i64,
+}
+
+impl<D: resource::sqlx::Database> reexport::res::GenResourceID<D> for GroupMember {
+    type Target = (i64, i64);
+
+    async fn gen_id() -> Result<(i64, i64), reexport::res::Error> {
+        Err(reexport::res::Error::GenIdFailure)
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+#[resource(
+    schema_name = "slep",
+    pg_table_name = "tag",
+    sqlite_table_name = "tag",
+    primary_key = "name: std::string::String, version: ::core::primitive::i32",
+    constraint = "slep_tag_pkey"
+)]
+pub struct Tag {
+    color: Option<String>,
+}
+
+impl<D: resource::sqlx::Database> GenResourceID<D> for Tag {
+    type Target = TagKey;
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+#[resource(
+    schema_name = "slep",
+    pg_table_name = "chunk",
+    sqlite_table_name = "chunk",
+    primary_key = "id: i64, hash: Vec<u8>",
+    constraint = "slep_chunk_pkey"
+)]
+pub struct Chunk {
+    len: i32,
+}
+
+impl<D: resource::sqlx::Database> GenResourceID<D> for Chunk {
+    type Target = ChunkKey;
+}
+
+/// Array columns, which only the native Postgres driver can bind.
+#[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+#[resource(
+    schema_name = "slep",
+    pg_table_name = "post",
+    sqlite_table_name = "post",
+    primary_key = "id:i64",
+    constraint = "slep_post_pkey",
+    drivers = "postgres"
+)]
+pub struct Post {
+    tags: Vec<String>,
+    scores: Option<Vec<i32>>,
+}
+
+impl<D: resource::sqlx::Database> GenResourceID<D> for Post {
+    type Target = i64;
+}
+
+/// Keyed by a digest, which only Postgres can bind as an array.
+#[derive(Deserialize, Serialize, PartialEq, Debug, resource_macros::Resource)]
+#[resource(
+    schema_name = "slep",
+    pg_table_name = "blob",
+    sqlite_table_name = "blob",
+    primary_key = "hash: [u8; 16]",
+    constraint = "slep_blob_pkey",
+    drivers = "postgres"
+)]
+pub struct Blob {
+    size: i64,
+}
+
+impl<D: resource::sqlx::Database> GenResourceID<D> for Blob {
+    type Target = [u8; 16];
+}
+
+fn assert_resource<DB: resource::sqlx::Database, R: Resource<DB>>() {}
+
+fn assert_native<DB: resource::sqlx::Database, R: Resource<DB, DB>>() {}
+
+#[test]
+fn derive_outside_crate() {
+    assert_resource::<resource::sqlx::Postgres, Group>();
+    assert_resource::<resource::sqlx::Sqlite, Group>();
+    assert_resource::<resource::sqlx::Postgres, GroupMember>();
+    assert_resource::<resource::sqlx::Sqlite, GroupMember>();
+    assert_eq!(
+        <Tag as Resource<resource::sqlx::Postgres>>::create_table_sql(),
+        r#"CREATE TABLE IF NOT EXISTS "slep"."tag" ( "name" TEXT NOT NULL, "version" INTEGER NOT NULL, "color" TEXT, CONSTRAINT "slep_tag_pkey" PRIMARY KEY ("name", "version") )"#
+    );
+    let key = TagKey {
+        name: "tls".to_string(),
+        version: 2,
+    };
+    assert_eq!(key.to_string(), "name=tls, version=2");
+    let key = ChunkKey {
+        id: 4,
+        hash: vec![0x0a, 0xff, 0x00],
+    };
+    assert_eq!(key.to_string(), "id=4, hash=0aff00");
+}
+
+#[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
+enum Posts {
+    Post(resource::Command<resource::GeneralAction<resource::sqlx::Postgres, Post>>),
+}
+
+#[test]
+fn derive_postgres_only() {
+    assert_native::<resource::sqlx::Postgres, Post>();
+    assert_eq!(
+        <Post as Resource<resource::sqlx::Postgres, resource::sqlx::Postgres>>::create_table_sql(),
+        r#"CREATE TABLE IF NOT EXISTS "slep"."post" ( "id" BIGINT NOT NULL, "tags" TEXT[] NOT NULL, "scores" INTEGER[], CONSTRAINT "slep_post_pkey" PRIMARY KEY ("id") )"#
+    );
+    // Commands of a native-only resource run on a native pool.
+    fn unawaited<F: std::future::Future>(_: F) {}
+    tokio_test::block_on(async {
+        let pool = resource::sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/resource")
+            .unwrap();
+        let post = Post {
+            tags: vec!["rust".to_string()],
+            scores: None,
+        };
+        let insert = resource::GeneralAction::Insert {
+            id: Some(1),
+            resource: post,
+        };
+        let cmds = resource::Commands::Single(Posts::post(1, insert, "Publish"));
+        unawaited(cmds.execute(&pool));
+    });
+}
+
+#[test]
+fn derive_byte_array_key() {
+    type Pg = resource::sqlx::Postgres;
+
+    assert_eq!(
+        <Blob as Resource<Pg, Pg>>::create_table_sql(),
+        r#"CREATE TABLE IF NOT EXISTS "slep"."blob" ( "hash" BYTEA NOT NULL, "size" BIGINT NOT NULL, CONSTRAINT "slep_blob_pkey" PRIMARY KEY ("hash") )"#
+    );
+    // Type-checks the generated statements against the key, without a server.
+    fn unawaited<F: std::future::Future>(_: F) {}
+    tokio_test::block_on(async {
+        let pool = resource::sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/resource")
+            .unwrap();
+        let blob = Blob { size: 3 };
+        unawaited(<Blob as Resource<Pg, Pg>>::insert(
+            &blob,
+            &Some([7; 16]),
+            &pool,
+        ));
+        unawaited(<Blob as Resource<Pg, Pg>>::fetch_by_id(&[7; 16], &pool));
+        unawaited(<Blob as Resource<Pg, Pg>>::drop(&[7; 16], &pool));
+    });
+}
+
+#[derive(Deserialize, Serialize, Debug, resource_macros::Resources)]
+enum Resources<'a> {
+    #[serde(borrow)]
+    Group(resource::Command<resource::GeneralAction<resource::sqlx::Postgres, Group<'a>>>),
+    GroupMember(resource::Command<resource::GeneralAction<resource::sqlx::Postgres, GroupMember>>),
+}
+
+#[test]
+fn derive_resources_outside_crate() {
+    use resource::Trace;
+
+    let drop = resource::GeneralAction::Drop((1, 2));
+    let member = Resources::group_member(3, drop, "Leave");
+    assert_eq!(member.trace(), Some((3, "Leave")));
+
+    let drop = resource::GeneralAction::<resource::sqlx::Postgres, Group>::Drop(1);
+    let group: Resources = resource::Command::new(4, drop, "Dismiss".to_string()).into();
+    assert!(matches!(group, Resources::Group(_)));
+    assert_eq!(group.trace(), Some((4, "Dismiss")));
+}